@@ -8,19 +8,20 @@
 //!
 //! The files will be downloaded to: `{pwd}/{host.id}/{local_path}`
 
+use super::{get_run_options, print_results, FailureFile};
 use crate::prelude::*;
 use crate::tasks::download;
 
 use clap::ArgMatches;
 
-pub fn run(hosts: Vec<Host>, matches: &ArgMatches) -> Result<()> {
+pub fn run(hosts: Vec<Host>, matches: &ArgMatches, failure_file: &FailureFile) -> Result<()> {
     let remote_path = get_path(matches.value_of("remote_path"))?;
     let local_path = get_path(matches.value_of("local_path"))?;
-    let parallel = matches.is_present("parallel");
+    let options = get_run_options(matches)?;
 
     let task = download::Task::new(remote_path, local_path);
-    let res = hosts.run_task(&task, parallel)?;
-    println!("{}", res);
+    let res = hosts.run_task_with(&task, &options)?;
+    print_results(&res, failure_file);
 
     Ok(())
 }
@@ -15,18 +15,19 @@
 //! $ tricorder -i inventory do -- echo "{host.id} says {host.vars.msg}"
 //! ```
 
+use super::{get_run_options, print_results, FailureFile};
 use crate::prelude::*;
 use crate::tasks::exec;
 
 use clap::ArgMatches;
 
-pub fn run(hosts: Vec<Host>, matches: &ArgMatches) -> Result<()> {
+pub fn run(hosts: Vec<Host>, matches: &ArgMatches, failure_file: &FailureFile) -> Result<()> {
     let cmd_tmpl = get_command(matches.values_of("cmd"));
-    let parallel = matches.is_present("parallel");
+    let options = get_run_options(matches)?;
 
     let task = exec::Task::new(cmd_tmpl);
-    let res = hosts.run_task(&task, parallel)?;
-    println!("{}", res);
+    let res = hosts.run_task_with(&task, &options)?;
+    print_results(&res, failure_file);
 
     Ok(())
 }
@@ -4,9 +4,10 @@
 //!
 //! | Variable | Description |
 //! | --- | --- |
-//! | `TRICORDER_INVENTORY` | Value of the `-i, --inventory` flag |
+//! | `TRICORDER_INVENTORY` | Values of the `-i, --inventory` flag, separated by `:` |
 //! | `TRICORDER_HOST_ID` | Value of the `-H, --host_id` flag |
 //! | `TRICORDER_HOST_TAGS` | Value of the `-t, --host_tags` flag |
+//! | `TRICORDER_EXCLUDE` | Value of the `--exclude` flag |
 //!
 //! Internally, calling `tricorder [global-options...] SUBCOMMAND [options...]`
 //! would be similar to:
@@ -15,19 +16,24 @@
 //! $ export TRICORDER_INVENTORY="..."
 //! $ export TRICORDER_HOST_ID="..."
 //! $ export TRICORDER_HOST_TAGS="..."
+//! $ export TRICORDER_EXCLUDE="..."
 //! $ tricorder-SUBCOMMAND [options...]
 //! ```
 
 use crate::prelude::Result;
 
 use clap::ArgMatches;
-use std::process::{exit, Command};
+use std::{
+    env,
+    process::{exit, Command},
+};
 
 pub fn run(
     command: &str,
-    inventory_arg: Option<&str>,
+    inventory_arg: &[&str],
     host_id_arg: Option<&str>,
     host_tags_arg: Option<&str>,
+    exclude_arg: Option<&str>,
     matches: &ArgMatches,
 ) -> Result<()> {
     let bin = format!("tricorder-{}", command);
@@ -38,9 +44,10 @@ pub fn run(
 
     let status = Command::new(bin)
         .args(args)
-        .env("TRICORDER_INVENTORY", inventory_arg.unwrap_or(""))
+        .env("TRICORDER_INVENTORY", env::join_paths(inventory_arg)?)
         .env("TRICORDER_HOST_ID", host_id_arg.unwrap_or(""))
         .env("TRICORDER_HOST_TAGS", host_tags_arg.unwrap_or(""))
+        .env("TRICORDER_EXCLUDE", exclude_arg.unwrap_or(""))
         .status()?;
 
     match status.code() {
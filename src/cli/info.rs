@@ -4,18 +4,36 @@
 //!
 //! ```shell
 //! $ tricorder -i inventory info
+//! $ tricorder -i inventory --fact-cache info --gather-facts
+//! ```
+//!
+//! The following options are available:
+//!
+//! | Flag | Description |
+//! | --- | --- |
+//! | `-g, --gather-facts` | If set, connect to the hosts to gather facts, and store them in the fact cache if enabled. |
+//!
+//! Once cached, facts can be used in templates of other commands:
+//!
+//! ```shell
+//! $ tricorder -i inventory --fact-cache do -- echo "running {host.facts.os.id}"
 //! ```
 
+use super::get_run_options;
 use crate::prelude::*;
 use crate::tasks::info;
 
 use clap::ArgMatches;
 
-pub fn run(hosts: Vec<Host>, matches: &ArgMatches) -> Result<()> {
-    let parallel = matches.is_present("parallel");
+pub fn run(hosts: Vec<Host>, matches: &ArgMatches, fact_cache: Option<FactCache>) -> Result<()> {
+    let options = get_run_options(matches)?;
 
-    let task = info::Task::new();
-    let res = hosts.run_task(&task, parallel)?;
+    let task = if matches.is_present("gather_facts") {
+        info::Task::new_gather_facts(fact_cache)
+    } else {
+        info::Task::new()
+    };
+    let res = hosts.run_task_with(&task, &options)?;
     println!("{}", res);
 
     Ok(())
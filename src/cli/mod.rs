@@ -8,13 +8,60 @@
 //!
 //! | Global flag | Description |
 //! | --- | --- |
-//! | `-i, --inventory <PATH>` | Path to a TOML inventory file or an executable producing a JSON inventory |
-//! | `-H, --host_id <STR>` | Specific host on which to perform the task |
-//! | `-t, --host_tags <STR>` | Boolean tag expression to select the hosts (example: `foo & !(bar | baz)`) |
+//! | `-i, --inventory <PATH>` | Path to a TOML or JSON inventory file, an executable producing a JSON inventory, or a directory of those (repeatable) |
+//! | `-H, --host_id <STR>` | Comma-separated list of host ids, glob patterns or `~`-prefixed regexes selecting the hosts (example: `web-*,db-0[1-3]`) |
+//! | `-t, --host_tags <STR>` | Boolean tag expression to select the hosts (example: `foo & !(bar | baz) & env=prod`) |
+//! | `--exclude <STR>` | Boolean tag expression of the hosts to exclude from the selection |
+//! | `--list-hosts` | Print the ids of the selected hosts, without running anything |
+//! | `--limit <HOSTS>` | Restrict the selected hosts to a comma-separated list of host ids, or to the ids listed in a file with `@PATH` |
+//! | `--rerun-failed` | Restrict the selected hosts to the ones which failed during the last run |
+//! | `--fact-cache` | Use the facts cached by `tricorder info --gather-facts` in templates |
+//! | `--fact-cache-dir <DIR>` | Directory of the fact cache, implies `--fact-cache` (default: `~/.cache/tricorder/facts`) |
+//! | `--fact-ttl <SECONDS>` | Duration after which cached facts are ignored (default: `86400`) |
+//!
+//! The subcommands running a task accept the following flags:
+//!
+//! | Flag | Description |
+//! | --- | --- |
+//! | `-p, --parallel` | Run the task concurrently on the hosts (of each batch) |
+//! | `--serial <COUNT>` | Run the task batch by batch, on `COUNT` hosts (or `COUNT%` of the hosts) at a time, at least 1 |
+//! | `--pause <DURATION>` | Duration to wait between two batches (example: `30s`, `5m`) |
+//! | `--batch-max-fail <COUNT>` | Skip the next batches once more than `COUNT` hosts (or `COUNT%` of the hosts) of a batch failed |
+//! | `--fail-fast` | Skip the remaining hosts as soon as a host failed |
+//! | `--max-fail <COUNT>` | Skip the remaining hosts once more than `COUNT` hosts (or `COUNT%` of the hosts) failed |
+//! | `--retries <N>` | Number of times the task is retried on a host after a connection error (default: `0`) |
+//! | `--retry-delay <DURATION>` | Duration to wait before the first retry, doubled after each attempt (default: `1s`) |
+//! | `--retry-on-exit-code` | Also retry the task when it returns a non-zero exit code |
+//! | `--failed-file <PATH>` | File where the ids of the failed hosts are written, and read by `--rerun-failed` (default: `~/.cache/tricorder/failed-<KEY>.txt`, with a key per set of inventories) |
+//!
+//! After a run of `do`, `script`, `upload`, `download` or `module` where some
+//! hosts failed (or were skipped), their ids are written to the failure file,
+//! one per line, so that they can be targeted by the next run with
+//! `--rerun-failed` (or `--limit @PATH`). A `--rerun-failed` run where no host
+//! failed removes the file, and other runs where no host failed keep it.
+//!
+//! The selected hosts are the ones matching both `-H` and `-t` (all the hosts
+//! if neither is provided), minus the ones matching `--exclude`:
+//!
+//! ```shell
+//! $ tricorder -i inventory -H 'web-*' -t 'prod' --exclude 'maintenance' --list-hosts
+//! ["web-01","web-02"]
+//! ```
+//!
+//! Besides tags, tag expressions can test the host's ID and variables:
+//!
+//! | Atom | Matches the hosts... |
+//! | --- | --- |
+//! | `web` | with the tag `web` |
+//! | `dc:*` | with a tag matching the glob pattern (`*`, `?` and `[...]`, negated with `[^...]`) |
+//! | `id:db-*` | whose ID matches the pattern (same syntax as `-H`) |
+//! | `env=prod` | whose variable `env` is equal to `prod` |
+//! | `vars.region~"eu-*"` | whose variable `region` matches the glob pattern |
+//! | `facts.os.id=debian` | whose cached fact `os.id` is equal to `debian` (requires `--fact-cache`) |
 //!
 //! > **NB:**
-//! >   - If `-H` is provided, `-t` will be ignored.
 //! >   - If `-i` is omitted, we assume an inventory with only `root@localhost:22`
+//! >   - If an inventory given with `-i` is missing or invalid, the command fails
 //! >   - The host needs only one tag from the list to match in order to be selected (boolean OR)
 
 pub mod download;
@@ -22,89 +69,552 @@ pub mod exec;
 pub mod external;
 pub mod info;
 pub mod module;
+pub mod ping;
+pub mod script;
 pub mod upload;
 
-use crate::prelude::{Host, HostId, Inventory, Result};
+use crate::prelude::{
+    is_failed, sha256_hex, Error, FactCache, Host, HostCount, HostId, HostPattern, Inventory,
+    Result, RunOptions,
+};
 
 use clap::ArgMatches;
+use serde_json::{json, Value};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 pub fn run(matches: ArgMatches) -> Result<()> {
-    let inventory_arg = matches.value_of("inventory");
+    let inventory_arg: Vec<&str> = matches.values_of("inventory").unwrap_or_default().collect();
     let host_id_arg = matches.value_of("host_id");
     let host_tags_arg = matches.value_of("host_tags");
+    let exclude_arg = matches.value_of("exclude");
+    let fact_cache = get_fact_cache(&matches)?;
+    let failure_file = get_failure_file(&matches, &inventory_arg);
+    let limit = get_limit(&matches, &failure_file)?;
+
+    let select_hosts = || -> Result<Vec<Host>> {
+        let inventory = get_inventory(&inventory_arg)?;
+        get_selected_hosts(
+            inventory,
+            host_id_arg,
+            host_tags_arg,
+            exclude_arg,
+            limit.as_ref(),
+            fact_cache.as_ref(),
+        )
+    };
+
+    if matches.is_present("list_hosts") {
+        let host_ids: Vec<String> = select_hosts()?
+            .into_iter()
+            .map(|host| host.id.to_string())
+            .collect();
+        println!("{}", json!(host_ids));
+        return Ok(());
+    }
 
     match matches.subcommand() {
-        Some(("info", sub_matches)) => {
-            let inventory = get_inventory(inventory_arg);
-            let hosts = get_host_list(inventory, host_id_arg, host_tags_arg)?;
-            info::run(hosts, sub_matches)
-        }
-        Some(("do", sub_matches)) => {
-            let inventory = get_inventory(inventory_arg);
-            let hosts = get_host_list(inventory, host_id_arg, host_tags_arg)?;
-            exec::run(hosts, sub_matches)
-        }
-        Some(("upload", sub_matches)) => {
-            let inventory = get_inventory(inventory_arg);
-            let hosts = get_host_list(inventory, host_id_arg, host_tags_arg)?;
-            upload::run(hosts, sub_matches)
-        }
+        Some(("info", sub_matches)) => info::run(select_hosts()?, sub_matches, fact_cache.clone()),
+        Some(("do", sub_matches)) => exec::run(select_hosts()?, sub_matches, &failure_file),
+        Some(("upload", sub_matches)) => upload::run(select_hosts()?, sub_matches, &failure_file),
         Some(("download", sub_matches)) => {
-            let inventory = get_inventory(inventory_arg);
-            let hosts = get_host_list(inventory, host_id_arg, host_tags_arg)?;
-            download::run(hosts, sub_matches)
+            download::run(select_hosts()?, sub_matches, &failure_file)
         }
-        Some(("module", sub_matches)) => {
-            let inventory = get_inventory(inventory_arg);
-            let hosts = get_host_list(inventory, host_id_arg, host_tags_arg)?;
-            module::run(hosts, sub_matches)
+        Some(("module", sub_matches)) if sub_matches.is_present("list") => {
+            module::list(&inventory_arg)
         }
-        Some((cmd, sub_matches)) => {
-            external::run(cmd, inventory_arg, host_id_arg, host_tags_arg, sub_matches)
+        Some(("module", sub_matches)) if sub_matches.is_present("gc") => {
+            module::gc(select_hosts()?, sub_matches)
         }
-        _ => {
-            unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`")
+        Some(("module", sub_matches)) => {
+            module::run(select_hosts()?, sub_matches, &inventory_arg, &failure_file)
         }
+        Some(("ping", sub_matches)) => ping::run(select_hosts()?, sub_matches),
+        Some(("script", sub_matches)) => script::run(select_hosts()?, sub_matches, &failure_file),
+        Some((cmd, sub_matches)) => external::run(
+            cmd,
+            &inventory_arg,
+            host_id_arg,
+            host_tags_arg,
+            exclude_arg,
+            sub_matches,
+        ),
+        None => Err(Box::new(Error::MissingInput(String::from(
+            "No subcommand provided, see `tricorder --help`",
+        )))),
     }
 }
 
-fn get_inventory(arg: Option<&str>) -> Inventory {
-    match arg {
-        Some(path) => match Inventory::from_file(path) {
-            Ok(inventory) => inventory,
-            Err(err) => {
-                eprintln!("{}, ignoring...", err);
-                Inventory::new()
-            }
-        },
-        None => {
-            eprintln!("No inventory provided, using empty inventory...");
-            Inventory::new()
-        }
+/// Load the inventories given with `-i`, or assume an inventory with only
+/// `root@localhost:22` if there is none.
+fn get_inventory(paths: &[&str]) -> Result<Inventory> {
+    if paths.is_empty() {
+        let mut inventory = Inventory::new();
+        inventory.add_host(Host::new(
+            Host::id("localhost")?,
+            String::from("localhost:22"),
+        ));
+        return Ok(inventory);
+    }
+
+    Inventory::from_paths(paths)
+}
+
+/// Load the cached facts into the hosts of the inventory, so that the tag
+/// expressions can test them, then select the hosts.
+fn get_selected_hosts(
+    inventory: Inventory,
+    host_id_arg: Option<&str>,
+    host_tags_arg: Option<&str>,
+    exclude_arg: Option<&str>,
+    limit: Option<&Vec<String>>,
+    fact_cache: Option<&FactCache>,
+) -> Result<Vec<Host>> {
+    let mut inventory = inventory;
+    let missing_facts = load_facts(&mut inventory.hosts, fact_cache)?;
+
+    let hosts = get_host_list(inventory, host_id_arg, host_tags_arg, exclude_arg)?;
+    let hosts = apply_limit(hosts, limit)?;
+
+    for host in hosts.iter().filter(|host| missing_facts.contains(&host.id)) {
+        eprintln!(
+            "No cached facts for host '{}', run `tricorder info --gather-facts` to gather them",
+            host.id.clone().to_string()
+        );
     }
+
+    Ok(hosts)
 }
 
+/// Select the hosts matching the `-H` patterns and the `-t` tag expression,
+/// minus the ones matching the `--exclude` tag expression.
 fn get_host_list(
     inventory: Inventory,
     host_id_arg: Option<&str>,
     host_tags_arg: Option<&str>,
+    exclude_arg: Option<&str>,
 ) -> Result<Vec<Host>> {
-    if let Some(host_id) = host_id_arg {
-        let hostlist = match inventory.get_host_by_id(HostId::new(host_id)?) {
-            Some(host) => {
-                vec![host]
-            }
-            None => {
-                eprintln!("Host '{}' not found in inventory, ignoring...", host_id);
-                vec![]
+    let mut hosts = match host_id_arg {
+        Some(host_id) => {
+            let patterns = HostPattern::parse_list(host_id)?;
+
+            for pattern in patterns.iter() {
+                if !inventory
+                    .hosts
+                    .iter()
+                    .any(|host| pattern.is_match(&host.id))
+                {
+                    eprintln!(
+                        "No host matching '{}' found in inventory, ignoring...",
+                        pattern.as_str()
+                    );
+                }
             }
+
+            inventory.get_hosts_by_patterns(&patterns)
+        }
+        None => inventory.hosts.clone(),
+    };
+
+    if let Some(host_tags) = host_tags_arg {
+        let tagged_hosts = inventory.get_hosts_by_tags(host_tags.to_string())?;
+        hosts.retain(|host| tagged_hosts.iter().any(|tagged| tagged.id == host.id));
+    }
+
+    if let Some(exclude) = exclude_arg {
+        let excluded_hosts = inventory.get_hosts_by_tags(exclude.to_string())?;
+        hosts.retain(|host| !excluded_hosts.iter().any(|excluded| excluded.id == host.id));
+    }
+
+    Ok(hosts)
+}
+
+fn get_limit(matches: &ArgMatches, failure_file: &FailureFile) -> Result<Option<Vec<String>>> {
+    let limit = if failure_file.rerun {
+        let path = &failure_file.path;
+
+        if !path.exists() {
+            return Err(Box::new(Error::FileNotFound(format!(
+                "No failure file '{}', the last run had no failed hosts",
+                path.display()
+            ))));
+        }
+
+        read_host_ids(path)?
+    } else {
+        match matches.value_of("limit") {
+            Some(limit) => match limit.strip_prefix('@') {
+                Some(path) => read_host_ids(Path::new(path))?,
+                None => limit
+                    .split(',')
+                    .map(|id| id.trim().to_string())
+                    .filter(|id| !id.is_empty())
+                    .collect(),
+            },
+            None => return Ok(None),
+        }
+    };
+
+    if limit.is_empty() {
+        return Err(Box::new(Error::InvalidArgument(String::from(
+            "No host ids to restrict the selection to",
+        ))));
+    }
+
+    Ok(Some(limit))
+}
+
+fn read_host_ids(path: &Path) -> Result<Vec<String>> {
+    if !path.exists() {
+        return Err(Box::new(Error::FileNotFound(format!(
+            "No such file: {}",
+            path.display()
+        ))));
+    }
+
+    let content = fs::read_to_string(path)?;
+    let ids = content
+        .lines()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    Ok(ids)
+}
+
+fn apply_limit(hosts: Vec<Host>, limit: Option<&Vec<String>>) -> Result<Vec<Host>> {
+    let ids = match limit {
+        Some(ids) => ids,
+        None => return Ok(hosts),
+    };
+
+    let hosts: Vec<Host> = hosts
+        .into_iter()
+        .filter(|host| ids.contains(&host.id.clone().to_string()))
+        .collect();
+
+    if hosts.is_empty() {
+        return Err(Box::new(Error::InvalidArgument(format!(
+            "None of the hosts {} is selected in the inventory",
+            json!(ids)
+        ))));
+    }
+
+    Ok(hosts)
+}
+
+/// File where the ids of the failed hosts of a run are saved.
+pub struct FailureFile {
+    path: PathBuf,
+    /// `true` if the run is restricted to the hosts of the file
+    rerun: bool,
+}
+
+/// Failure file of the subcommand: its `--failed-file` flag, or the default
+/// one of the inventories.
+fn get_failure_file(matches: &ArgMatches, inventory_arg: &[&str]) -> FailureFile {
+    let path = match matches.subcommand() {
+        Some((_, sub_matches)) if sub_matches.is_valid_arg("failed_file") => {
+            sub_matches.value_of("failed_file")
+        }
+        _ => None,
+    };
+
+    FailureFile {
+        path: match path {
+            Some(path) => PathBuf::from(path),
+            None => default_failed_file(inventory_arg),
+        },
+        rerun: matches.is_present("rerun_failed"),
+    }
+}
+
+/// Default failure file: `failed-<KEY>.txt` next to the default fact cache,
+/// where `KEY` identifies the inventories, so that the runs on different
+/// inventories do not share their failures.
+fn default_failed_file(inventory_arg: &[&str]) -> PathBuf {
+    let mut paths: Vec<String> = inventory_arg
+        .iter()
+        .map(|path| {
+            fs::canonicalize(path)
+                .unwrap_or_else(|_| PathBuf::from(path))
+                .display()
+                .to_string()
+        })
+        .collect();
+    paths.sort();
+
+    let key = sha256_hex(paths.join("\n").as_bytes());
+    FactCache::default_dir().with_file_name(format!("failed-{}.txt", &key[..16]))
+}
+
+/// Print the results of a task, and update the failure file.
+///
+/// A failure to update the file is reported, but does not fail the run.
+pub(crate) fn print_results(res: &Value, failure_file: &FailureFile) {
+    println!("{}", res);
+
+    if let Err(err) = save_failed_hosts(&failure_file.path, res, failure_file.rerun) {
+        eprintln!(
+            "Could not update the failure file '{}': {}",
+            failure_file.path.display(),
+            err
+        );
+    }
+}
+
+/// Write the ids of the hosts which failed (or were skipped) to the failure
+/// file, one per line.
+///
+/// If no host failed, the file is removed after a rerun of its hosts, and
+/// kept otherwise, since the run may not have selected them.
+fn save_failed_hosts(path: &Path, res: &Value, rerun: bool) -> Result<()> {
+    let failed_hosts: Vec<&str> = res
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|result| is_failed(result))
+        .filter_map(|result| result["host"].as_str())
+        .collect();
+
+    if failed_hosts.is_empty() {
+        if !rerun {
+            return Ok(());
+        }
+
+        return match fs::remove_file(path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(Box::new(err)),
+            _ => Ok(()),
         };
-        return Ok(hostlist);
     }
 
-    if let Some(host_tags) = host_tags_arg {
-        return Ok(inventory.get_hosts_by_tags(host_tags.to_string())?);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
     }
+    fs::write(path, format!("{}\n", failed_hosts.join("\n")))?;
 
-    return Ok(inventory.hosts.clone());
+    Ok(())
+}
+
+fn get_fact_cache(matches: &ArgMatches) -> Result<Option<FactCache>> {
+    if !matches.is_present("fact_cache") && !matches.is_present("fact_cache_dir") {
+        return Ok(None);
+    }
+
+    let dir = match matches.value_of("fact_cache_dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => FactCache::default_dir(),
+    };
+    let ttl = match matches.value_of("fact_ttl") {
+        Some(secs) => Duration::from_secs(secs.parse()?),
+        None => Duration::from_secs(24 * 60 * 60),
+    };
+
+    Ok(Some(FactCache::new(dir, ttl)))
+}
+
+/// Load the cached facts into the hosts, and return the ids of the hosts
+/// without cached facts.
+fn load_facts(hosts: &mut [Host], fact_cache: Option<&FactCache>) -> Result<Vec<HostId>> {
+    let mut missing_facts = vec![];
+
+    if let Some(cache) = fact_cache {
+        for host in hosts.iter_mut() {
+            if !cache.load(host)? {
+                missing_facts.push(host.id.clone());
+            }
+        }
+    }
+
+    Ok(missing_facts)
+}
+
+/// Build the `RunOptions` from the flags common to all subcommands running a
+/// task.
+pub(crate) fn get_run_options(matches: &ArgMatches) -> Result<RunOptions> {
+    let mut options = RunOptions::new();
+    options.set_parallel(matches.is_present("parallel"));
+
+    if let Some(batch_size) = matches.value_of("serial") {
+        options.set_batch_size(parse_batch_size(batch_size)?);
+    }
+
+    if let Some(pause) = matches.value_of("pause") {
+        options.set_batch_pause(parse_duration(pause)?);
+    }
+
+    if let Some(max_failures) = matches.value_of("batch_max_fail") {
+        options.set_batch_max_failures(max_failures.parse()?);
+    }
+
+    if matches.is_present("fail_fast") {
+        options.set_fail_fast();
+    }
+
+    if let Some(max_failures) = matches.value_of("max_fail") {
+        options.set_max_failures(max_failures.parse()?);
+    }
+
+    if let Some(retries) = matches.value_of("retries") {
+        let retries = retries.parse().map_err(|_| {
+            Error::InvalidArgument(format!(
+                "Invalid number of retries '{}', expected a positive number",
+                retries
+            ))
+        })?;
+        options.set_retries(retries);
+    }
+
+    if let Some(retry_delay) = matches.value_of("retry_delay") {
+        options.set_retry_delay(parse_duration(retry_delay)?);
+    }
+
+    options.set_retry_on_exit_code(matches.is_present("retry_on_exit_code"));
+
+    Ok(options)
+}
+
+/// Parse the `--serial` batch size, which must select at least one host.
+fn parse_batch_size(src: &str) -> Result<HostCount> {
+    let batch_size: HostCount = src.parse()?;
+
+    if batch_size.of(100) == 0 {
+        return Err(Box::new(Error::InvalidArgument(format!(
+            "Invalid batch size '{}', expected at least 1 host or 1%",
+            src
+        ))));
+    }
+
+    Ok(batch_size)
+}
+
+/// Parse a duration such as `500ms`, `30s`, `5m` or `1h` (seconds if no unit
+/// is given).
+pub(crate) fn parse_duration(src: &str) -> Result<Duration> {
+    let src = src.trim();
+    let split_at = src.find(|c: char| !c.is_ascii_digit()).unwrap_or(src.len());
+    let (amount, unit) = src.split_at(split_at);
+
+    let invalid = || {
+        Error::InvalidArgument(format!(
+            "Invalid duration '{}', expected a number followed by ms, s, m or h (example: 30s)",
+            src
+        ))
+    };
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+
+    match unit {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "" | "s" => Ok(Duration::from_secs(amount)),
+        "m" => Ok(Duration::from_secs(amount * 60)),
+        "h" => Ok(Duration::from_secs(amount * 60 * 60)),
+        _ => Err(Box::new(invalid())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_util::TestDir;
+    use std::collections::HashMap;
+
+    #[test]
+    fn failed_hosts_should_be_read_back() {
+        let dir = TestDir::new("failed-hosts-round-trip");
+        let path = dir.join("failed.txt");
+        let res = json!([
+            {"host": "web-1", "success": true},
+            {"host": "web-2", "success": false},
+            {"host": "web-3", "skipped": true},
+        ]);
+
+        save_failed_hosts(&path, &res, false).unwrap();
+        assert_eq!(read_host_ids(&path).unwrap(), vec!["web-2", "web-3"]);
+
+        let hosts = vec![
+            Host::new(Host::id("web-1").unwrap(), String::from("10.0.1.1:22")),
+            Host::new(Host::id("web-2").unwrap(), String::from("10.0.1.2:22")),
+        ];
+        let limit = read_host_ids(&path).unwrap();
+        let hosts = apply_limit(hosts, Some(&limit)).unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].id, Host::id("web-2").unwrap());
+
+        // a clean run on other hosts keeps the failures
+        let res = json!([{"host": "web-1", "success": true}]);
+        save_failed_hosts(&path, &res, false).unwrap();
+        assert_eq!(read_host_ids(&path).unwrap(), vec!["web-2", "web-3"]);
+
+        // a clean rerun of the failed hosts removes the file
+        let res = json!([{"host": "web-2", "success": true}]);
+        save_failed_hosts(&path, &res, true).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn default_failed_file_should_depend_on_the_inventories() {
+        let web = default_failed_file(&["web.toml"]);
+        let db = default_failed_file(&["db.toml"]);
+
+        assert_ne!(web, db);
+        assert_eq!(web, default_failed_file(&["web.toml"]));
+        assert_eq!(
+            default_failed_file(&["web.toml", "db.toml"]),
+            default_failed_file(&["db.toml", "web.toml"])
+        );
+        assert_eq!(web.parent(), FactCache::default_dir().parent());
+    }
+
+    #[test]
+    fn hosts_should_be_selected_on_their_facts() {
+        let dir = TestDir::new("select-on-facts");
+        let cache = FactCache::new(dir.to_path_buf(), Duration::from_secs(60));
+
+        let mut inventory = Inventory::new();
+        for id in ["web-1", "web-2"] {
+            inventory.add_host(Host::new(Host::id(id).unwrap(), format!("{}:22", id)));
+        }
+
+        let mut facts = HashMap::new();
+        facts.insert(String::from("os"), json!({"id": "debian"}));
+        cache.set(&Host::id("web-2").unwrap(), &facts).unwrap();
+
+        let hosts = get_selected_hosts(
+            inventory,
+            None,
+            Some("facts.os.id=debian"),
+            None,
+            None,
+            Some(&cache),
+        )
+        .unwrap();
+
+        assert_eq!(hosts.len(), 1);
+        assert_eq!(hosts[0].id, Host::id("web-2").unwrap());
+        assert_eq!(hosts[0].facts, facts);
+    }
+
+    #[test]
+    fn apply_limit_should_fail_when_no_host_is_selected() {
+        let hosts = vec![Host::new(
+            Host::id("web-1").unwrap(),
+            String::from("10.0.1.1:22"),
+        )];
+        let limit = vec![String::from("db-1")];
+
+        assert!(apply_limit(hosts, Some(&limit)).is_err());
+    }
+
+    #[test]
+    fn parse_batch_size_should_reject_empty_batches() {
+        assert_eq!(parse_batch_size("2").unwrap(), HostCount::Absolute(2));
+        assert_eq!(parse_batch_size("25%").unwrap(), HostCount::Percent(25));
+        assert!(parse_batch_size("0").is_err());
+        assert!(parse_batch_size("0%").is_err());
+    }
 }
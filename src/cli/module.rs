@@ -15,27 +15,212 @@
 //! ```shell
 //! $ tricorder -i inventory module --data <DATA_FILE_PATH> --module <MODULE_PATH>
 //! $ tricorder -i inventory module --module <MODULE_PATH>
+//! $ tricorder -i inventory module -T --data <DATA_FILE_PATH> --module <MODULE_PATH>
+//! $ tricorder -i inventory module --force-upload --module <MODULE_PATH>
+//! $ tricorder -i inventory module --module <MODULE_NAME>
+//! $ tricorder -i inventory module -m <MODULE_NAME> -a key=value -a nested.key=value
+//! $ tricorder -i inventory module -m <MODULE_NAME> --data-json '{"key": "value"}'
+//! $ tricorder -i inventory module --list
+//! $ tricorder -i inventory module --workspace /opt/tricorder --cleanup -m <MODULE_NAME>
+//! $ tricorder -i inventory module --gc --max-age 7 [--dry-run]
 //! ```
 //!
+//! Modules are uploaded to the `modules/` subdirectory of the remote workspace
+//! (`-w, --workspace`, defaults to `~/.local/tricorder`), and are removed after
+//! their execution if `--cleanup` is set.
+//!
+//! `--gc` lists the modules uploaded on the selected hosts, and removes the
+//! ones which were neither uploaded nor executed for `--max-age` days
+//! (defaults to 30). The result of each host lists the modules actually
+//! removed as `pruned`. With `--dry-run`, the stale modules are only listed.
+//!
+//! With `-T, --template`, the data file is rendered as a template with the
+//! current host as context data before being parsed (string values are escaped
+//! as JSON strings, the curly braces of JSON objects must be escaped as `\{`).
+//!
+//! The data sent to the module is built by merging, in order of precedence:
+//!
+//!  1. the arguments given with `--data-json`, then `-a, --arg` (values are
+//!     parsed as JSON if possible, as strings otherwise)
+//!  2. the host variable `host.vars.module_<module_name>`
+//!  3. the data file
+//!
+//! With `-v, --verbose`, the resulting data is included in the result of each
+//! host as `data`.
+//!
+//! If `--module` is not the path to an existing file, the module is searched by
+//! name in the following directories:
+//!
+//!  - each directory listed in the `TRICORDER_MODULE_PATH` environment
+//!    variable (separated by `:`)
+//!  - the `modules/` directory next to each inventory file (or directory)
+//!
+//! `--list` prints the modules available in those directories as a JSON
+//! document. A module can embed a description with a comment line
+//! `description: ...` in its first 10 lines:
+//!
+//! ```shell
+//! #!/bin/bash
+//! # description: Create an nginx virtual host
+//! ```
+//!
+//! If the module writes a JSON document on `stdout`, its `changed`, `failed`,
+//! `msg` and `diff` keys are lifted into the result of each host (see
+//! `tricorder::tasks::module`), and a summary of the changed and failed hosts
+//! is written on `stderr`.
+//!
+//! The module is only uploaded to hosts which do not already have an identical
+//! copy of it (compared by SHA-256 digest), unless `--force-upload` is set.
 
+use super::{get_run_options, print_results, FailureFile};
 use crate::prelude::*;
 use crate::tasks::module;
 
 use clap::ArgMatches;
+use is_executable::IsExecutable;
+use serde_json::{json, Value};
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
-pub fn run(hosts: Vec<Host>, matches: &ArgMatches) -> Result<()> {
+pub fn run(
+    hosts: Vec<Host>,
+    matches: &ArgMatches,
+    inventory_arg: &[&str],
+    failure_file: &FailureFile,
+) -> Result<()> {
     let data_path = get_data_path(matches.value_of("data_file_path"));
-    let module_path = get_path(matches.value_of("module"))?;
-    let parallel = matches.is_present("parallel");
+    let module_dirs = get_module_dirs(inventory_arg);
+    let module_path = resolve_module(&get_path(matches.value_of("module"))?, &module_dirs)?;
+    let options = get_run_options(matches)?;
+
+    let mut task = module::Task::new(data_path, module_path);
+    task.set_template(matches.is_present("template"))
+        .set_force_upload(matches.is_present("force_upload"))
+        .set_verbose(matches.is_present("verbose"))
+        .set_cleanup(matches.is_present("cleanup"));
+
+    if let Some(workspace) = matches.value_of("workspace") {
+        task.set_workspace(workspace.to_string());
+    }
+
+    if let Some(args) = get_args(matches)? {
+        task.set_args(args);
+    }
 
-    let task = module::Task::new(data_path, module_path);
+    let res = hosts.run_task_with(&task, &options)?;
+    print_results(&res, failure_file);
+    print_summary(&res);
 
-    let res = hosts.run_task(&task, parallel)?;
+    Ok(())
+}
+
+/// Remove the stale modules from the hosts.
+pub fn gc(hosts: Vec<Host>, matches: &ArgMatches) -> Result<()> {
+    let max_age_days = match matches.value_of("max_age") {
+        Some(days) => days.parse()?,
+        None => 30,
+    };
+    let options = get_run_options(matches)?;
+
+    let mut task = module::GcTask::new(max_age_days);
+    task.set_dry_run(matches.is_present("dry_run"));
+
+    if let Some(workspace) = matches.value_of("workspace") {
+        task.set_workspace(workspace.to_string());
+    }
+
+    let res = hosts.run_task_with(&task, &options)?;
     println!("{}", res);
 
     Ok(())
 }
 
+/// Print the modules found in the module search path.
+pub fn list(inventory_arg: &[&str]) -> Result<()> {
+    let modules = find_modules(&get_module_dirs(inventory_arg))?;
+    println!("{}", json!(modules));
+
+    Ok(())
+}
+
+fn find_modules(module_dirs: &[PathBuf]) -> Result<Vec<Value>> {
+    let mut seen = HashSet::new();
+    let mut modules = vec![];
+
+    for dir in module_dirs {
+        let mut entries = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<std::io::Result<Vec<PathBuf>>>()?,
+            Err(_) => continue,
+        };
+        entries.sort();
+
+        for path in entries {
+            let name = match path.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => continue,
+            };
+
+            // modules found earlier in the search path shadow the next ones
+            if is_module(&path) && seen.insert(name.clone()) {
+                modules.push(json!({
+                  "name": name,
+                  "path": path,
+                  "description": get_description(&path)?,
+                }));
+            }
+        }
+    }
+
+    Ok(modules)
+}
+
+/// Only the executable files of the module search path are modules.
+fn is_module(path: &Path) -> bool {
+    path.is_file() && path.is_executable()
+}
+
+/// Summarize on `stderr` the hosts changed or failed by the module, and the
+/// hosts on which the module could not be run.
+fn print_summary(res: &Value) {
+    let results = res.as_array().cloned().unwrap_or_default();
+
+    let changed = hosts_matching(&results, |result| result["info"]["changed"] == json!(true));
+    let failed = hosts_matching(&results, |result| result["info"]["failed"] == json!(true));
+    let errored = hosts_matching(&results, |result| result["success"] == json!(false));
+
+    eprintln!(
+        "{} hosts: {} changed, {} failed, {} errors",
+        results.len(),
+        changed.len(),
+        failed.len(),
+        errored.len()
+    );
+
+    for (label, hosts) in [
+        ("Changed", changed),
+        ("Failed", failed),
+        ("Errors", errored),
+    ] {
+        if !hosts.is_empty() {
+            eprintln!("{}: {}", label, hosts.join(", "));
+        }
+    }
+}
+
+fn hosts_matching(results: &[Value], predicate: impl Fn(&Value) -> bool) -> Vec<String> {
+    results
+        .iter()
+        .filter(|result| predicate(result))
+        .filter_map(|result| result["host"].as_str().map(String::from))
+        .collect()
+}
+
 fn get_path(arg: Option<&str>) -> Result<String> {
     if let Some(path) = arg {
         Ok(String::from(path))
@@ -46,6 +231,176 @@ fn get_path(arg: Option<&str>) -> Result<String> {
     }
 }
 
+fn get_module_dirs(inventory_arg: &[&str]) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+
+    if let Some(paths) = env::var_os("TRICORDER_MODULE_PATH") {
+        dirs.extend(env::split_paths(&paths));
+    }
+
+    for parent in inventory_arg
+        .iter()
+        .filter_map(|path| Path::new(path).parent())
+    {
+        dirs.push(parent.join("modules"));
+    }
+
+    dirs
+}
+
+fn resolve_module(module: &str, module_dirs: &[PathBuf]) -> Result<String> {
+    if Path::new(module).is_file() || module.contains('/') {
+        return Ok(module.to_string());
+    }
+
+    let found = module_dirs
+        .iter()
+        .map(|dir| dir.join(module))
+        .find(|path| is_module(path));
+
+    match found {
+        Some(path) => Ok(path.to_string_lossy().to_string()),
+        None => {
+            let dirs: Vec<String> = module_dirs
+                .iter()
+                .map(|dir| dir.to_string_lossy().to_string())
+                .collect();
+
+            Err(Box::new(Error::FileNotFound(format!(
+                "Module '{}' not found in module search path: [{}]",
+                module,
+                dirs.join(", ")
+            ))))
+        }
+    }
+}
+
+/// Extract the `description: ...` comment from the first lines of a module.
+fn get_description(path: &Path) -> Result<Option<String>> {
+    let mut head = vec![];
+    fs::File::open(path)?.take(4096).read_to_end(&mut head)?;
+
+    let description = String::from_utf8_lossy(&head)
+        .lines()
+        .take(10)
+        .map(|line| line.trim_start_matches(|c: char| "#/;-! ".contains(c)))
+        .find_map(|line| line.strip_prefix("description:"))
+        .map(|description| description.trim().to_string());
+
+    Ok(description)
+}
+
+fn get_args(matches: &ArgMatches) -> Result<Option<Value>> {
+    let key_values: Vec<&str> = matches
+        .values_of("args")
+        .map(|vals| vals.collect())
+        .unwrap_or_default();
+
+    let mut args = match matches.value_of("data_json") {
+        Some(src) => {
+            let args: Value = serde_json::from_str(src)?;
+
+            if !args.is_object() {
+                return Err(Box::new(Error::InvalidArgument(
+                    "--data-json should be a JSON object".to_string(),
+                )));
+            }
+
+            args
+        }
+        None if key_values.is_empty() => {
+            return Ok(None);
+        }
+        None => json!({}),
+    };
+
+    for key_value in key_values {
+        let (key, val) = key_value.split_once('=').ok_or_else(|| {
+            Error::InvalidArgument(format!(
+                "Module argument '{}' should be in the form key=value",
+                key_value
+            ))
+        })?;
+        let val = serde_json::from_str(val).unwrap_or_else(|_| json!(val));
+
+        set_path(&mut args, key, val);
+    }
+
+    Ok(Some(args))
+}
+
+/// Set `val` at the dot-separated path `key`, creating the intermediate
+/// objects if needed.
+fn set_path(target: &mut Value, key: &str, val: Value) {
+    let mut current = target;
+
+    for part in key.split('.') {
+        if !current.is_object() {
+            *current = json!({});
+        }
+
+        current = current
+            .as_object_mut()
+            .unwrap()
+            .entry(part)
+            .or_insert(Value::Null);
+    }
+
+    *current = val;
+}
+
 fn get_data_path(arg: Option<&str>) -> Option<String> {
     arg.map(String::from)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn setup_module_dir(name: &str) -> TestDir {
+        let dir = TestDir::new(name);
+
+        let module = dir.join("nginx");
+        fs::write(&module, "#!/bin/sh\n# description: Configure nginx\n").unwrap();
+        fs::set_permissions(&module, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(dir.join("notes.txt"), "Modules\n").unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn resolve_module_should_only_find_executables() {
+        let dir = setup_module_dir("resolve-module");
+        let module_dirs = vec![dir.to_path_buf()];
+
+        let nginx = resolve_module("nginx", &module_dirs);
+        let notes = resolve_module("notes.txt", &module_dirs);
+
+        assert_eq!(nginx.unwrap(), dir.join("nginx").to_string_lossy());
+        assert!(notes.is_err());
+    }
+
+    #[test]
+    fn find_modules_should_list_what_resolve_module_finds() {
+        let dir = setup_module_dir("find-modules");
+        let module_dirs = vec![dir.to_path_buf()];
+
+        let modules = find_modules(&module_dirs).unwrap();
+        let resolved: Vec<bool> = ["nginx", "notes.txt"]
+            .iter()
+            .map(|name| resolve_module(name, &module_dirs).is_ok())
+            .collect();
+
+        assert_eq!(
+            modules,
+            vec![json!({
+              "name": "nginx",
+              "path": dir.join("nginx"),
+              "description": "Configure nginx",
+            })]
+        );
+        assert_eq!(resolved, vec![true, false]);
+    }
+}
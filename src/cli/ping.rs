@@ -0,0 +1,36 @@
+//! Command Line Interface to the `tricorder::tasks::ping` task
+//!
+//! Example:
+//!
+//! ```shell
+//! $ tricorder -i inventory ping -p
+//! ```
+//!
+//! Reports, for each host, the latency of the TCP connection, the SSH
+//! handshake, the authentication and the execution of a no-op command, or the
+//! category of the error if the host is unreachable.
+//!
+//! The TCP connection, and each step of the SSH session, times out after the
+//! host's `connect_timeout`, or after `--connect-timeout` seconds (default: 10)
+//! if the host has none.
+
+use super::get_run_options;
+use crate::prelude::*;
+use crate::tasks::ping;
+
+use clap::ArgMatches;
+
+pub fn run(hosts: Vec<Host>, matches: &ArgMatches) -> Result<()> {
+    let options = get_run_options(matches)?;
+
+    let mut task = ping::Task::new();
+
+    if let Some(connect_timeout) = matches.value_of("connect_timeout") {
+        task.set_connect_timeout(connect_timeout.parse()?);
+    }
+
+    let res = hosts.run_task_with(&task, &options)?;
+    println!("{}", res);
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//! Run a local script on multiple remote hosts, without leaving files behind.
+//!
+//! Usage:
+//!
+//! ```shell
+//! $ tricorder -i inventory script SCRIPT_PATH [-- ARGS...]
+//! $ tricorder -i inventory script -T SCRIPT_PATH [-- ARGS...]
+//! $ tricorder -i inventory script --interpreter "python3 -" SCRIPT_PATH [-- ARGS...]
+//! ```
+//!
+//! The script is streamed to the standard input of its interpreter, detected
+//! from the script's shebang (see `tricorder::tasks::script`).
+//!
+//! The following options are available:
+//!
+//! | Flag | Description |
+//! | --- | --- |
+//! | `-T, --template` | If set, treats `SCRIPT_PATH` as a template with the current host as input data. |
+//! | `--interpreter <CMD>` | Command reading the script from its standard input, instead of the one detected from the shebang. |
+
+use super::{get_run_options, print_results, FailureFile};
+use crate::prelude::*;
+use crate::tasks::script;
+
+use clap::ArgMatches;
+
+pub fn run(hosts: Vec<Host>, matches: &ArgMatches, failure_file: &FailureFile) -> Result<()> {
+    let script_path = get_path(matches.value_of("script_path"))?;
+    let args = get_args(matches.values_of("args"));
+    let options = get_run_options(matches)?;
+
+    let mut task = if matches.is_present("template") {
+        script::Task::new_template(script_path, args)
+    } else {
+        script::Task::new_file(script_path, args)
+    };
+
+    if let Some(interpreter) = matches.value_of("interpreter") {
+        task.set_interpreter(interpreter.to_string());
+    }
+
+    let res = hosts.run_task_with(&task, &options)?;
+    print_results(&res, failure_file);
+
+    Ok(())
+}
+
+fn get_path(arg: Option<&str>) -> Result<String> {
+    if let Some(path) = arg {
+        Ok(String::from(path))
+    } else {
+        Err(Box::new(Error::MissingInput(
+            "No input file provided".to_string(),
+        )))
+    }
+}
+
+fn get_args(arg: Option<clap::Values<'_>>) -> Vec<String> {
+    arg.map(|vals| vals.map(String::from).collect())
+        .unwrap_or_default()
+}
@@ -15,6 +15,7 @@
 //! | --- | --- |
 //! | `-T, --template` | If set, treats `LOCAL_PATH` as a template with the current host as input data. |
 
+use super::{get_run_options, print_results, FailureFile};
 use crate::prelude::*;
 use crate::tasks::upload;
 
@@ -22,11 +23,11 @@ use clap::ArgMatches;
 use file_mode::Mode;
 use std::convert::TryFrom;
 
-pub fn run(hosts: Vec<Host>, matches: &ArgMatches) -> Result<()> {
+pub fn run(hosts: Vec<Host>, matches: &ArgMatches, failure_file: &FailureFile) -> Result<()> {
     let local_path = get_path(matches.value_of("local_path"))?;
     let remote_path = get_path(matches.value_of("remote_path"))?;
     let file_mode = get_file_mode(matches.value_of("file_mode"))?;
-    let parallel = matches.is_present("parallel");
+    let options = get_run_options(matches)?;
 
     let task = if matches.is_present("template") {
         upload::Task::new_template(local_path, remote_path, file_mode)
@@ -34,8 +35,8 @@ pub fn run(hosts: Vec<Host>, matches: &ArgMatches) -> Result<()> {
         upload::Task::new_file(local_path, remote_path, file_mode)
     };
 
-    let res = hosts.run_task(&task, parallel)?;
-    println!("{}", res);
+    let res = hosts.run_task_with(&task, &options)?;
+    print_results(&res, failure_file);
 
     Ok(())
 }
@@ -36,3 +36,6 @@
 pub mod cli;
 pub mod prelude;
 pub mod tasks;
+
+#[cfg(test)]
+mod test_util;
@@ -1,37 +1,74 @@
-use clap::{arg, command, Command};
-use tricorder::{cli, prelude::Result};
+use clap::{arg, command, Arg, Command};
+use tricorder::cli;
 
-fn main() -> Result<()> {
+use std::process::exit;
+
+fn main() {
     let matches = command!()
     .propagate_version(true)
-    .subcommand_required(true)
+    .arg_required_else_help(true)
     .allow_external_subcommands(true)
     .allow_invalid_utf8_for_external_subcommands(true)
     .arg(
-      arg!(inventory: -i --inventory <FILE> "Path to TOML inventory file or program producing JSON inventory")
+      arg!(inventory: -i --inventory <PATH> "Path to TOML or JSON inventory file, program producing JSON inventory, or directory of those (can be repeated)")
+      .required(false)
+      .multiple_occurrences(true)
+    )
+    .arg(
+      arg!(host_id: -H --host_id <STR> "Comma-separated list of host ids, glob patterns (web-*) or regexes prefixed with ~ (~^web-\\d+$) of the hosts to connect to")
+      .required(false)
+    )
+    .arg(
+      arg!(host_tags: -t --host_tags <STR> "Boolean expression of tags, ids (id:web-*) and variables (env=prod) identifying the hosts to connect to")
+      .required(false)
+    )
+    .arg(
+      arg!(exclude: --exclude <STR> "Boolean tag expression identifying the hosts to exclude")
       .required(false)
     )
     .arg(
-      arg!(host_id: -H --host_id <STR> "Identifier of the host to connect to")
+      arg!(list_hosts: --"list-hosts" "If set, print the ids of the selected hosts without running anything")
+    )
+    .arg(
+      arg!(limit: --limit <HOSTS> "Restrict the selected hosts to a comma-separated list of host ids, or to the ids listed in a file with @PATH")
       .required(false)
     )
     .arg(
-      arg!(host_tags: -t --host_tags <STR> "Comma-separated list of tags identifying the hosts to connect to")
+      arg!(rerun_failed: --"rerun-failed" "Restrict the selected hosts to the ones which failed during the last run")
+      .conflicts_with("limit")
+    )
+    .arg(
+      arg!(fact_cache: --"fact-cache" "If set, use the facts cached by `info --gather-facts` in templates")
+    )
+    .arg(
+      arg!(fact_cache_dir: --"fact-cache-dir" <DIR> "Directory of the fact cache, implies --fact-cache (default: ~/.cache/tricorder/facts)")
+      .required(false)
+    )
+    .arg(
+      arg!(fact_ttl: --"fact-ttl" <SECONDS> "Duration after which cached facts are ignored (default: 86400)")
       .required(false)
     )
     .subcommand(
       Command::new("info")
         .about("Gather information about hosts in the inventory")
+        .args(runner_args())
         .arg(
-          arg!(parallel: -p --parallel "If set, the task will be executed concurrently")
+          arg!(gather_facts: -g --"gather-facts" "If set, connect to the hosts to gather facts")
         )
     )
     .subcommand(
-      Command::new("do")
-        .about("Execute a command on multiple hosts")
+      Command::new("ping")
+        .about("Check connectivity and authentication to hosts")
+        .args(runner_args())
         .arg(
-          arg!(parallel: -p --parallel "If set, the task will be executed concurrently")
+          arg!(connect_timeout: --"connect-timeout" <SECONDS> "Timeout of the connection and of each SSH step to the hosts without their own connect_timeout (default: 10)")
+          .required(false)
         )
+    )
+    .subcommand(
+      Command::new("do")
+        .about("Execute a command on multiple hosts")
+        .args(runner_args())
         .arg(
           arg!(cmd: [COMMAND] "Command to run on each host")
           .last(true)
@@ -39,11 +76,30 @@ fn main() -> Result<()> {
         )
     )
     .subcommand(
-      Command::new("upload")
-        .about("Upload a file to multiple hosts")
+      Command::new("script")
+        .about("Run a local script on multiple hosts")
+        .args(runner_args())
         .arg(
-          arg!(parallel: -p --parallel "If set, the task will be executed concurrently")
+          arg!(template: -T --template "If set, the script is a template with the current host as context data")
         )
+        .arg(
+          arg!(interpreter: --interpreter <CMD> "Command reading the script from its standard input (default: detected from the shebang)")
+          .required(false)
+        )
+        .arg(
+          arg!(script_path: [SCRIPT_PATH] "Path on local host to the script to run")
+          .required(true)
+        )
+        .arg(
+          arg!(args: [ARGS] "Arguments given to the script")
+          .last(true)
+          .multiple_values(true)
+        )
+    )
+    .subcommand(
+      Command::new("upload")
+        .about("Upload a file to multiple hosts")
+        .args(runner_args())
         .arg(
           arg!(template: -T --template "If set, the file is a template with the current host as context data")
         )
@@ -62,9 +118,7 @@ fn main() -> Result<()> {
     .subcommand(
       Command::new("download")
         .about("Download a file from multiple hosts")
-        .arg(
-          arg!(parallel: -p --parallel "If set, the task will be executed concurrently")
-        )
+        .args(runner_args())
         .arg(
           arg!(remote_path: [REMOTE_PATH] "Path to the file on the remote host")
           .required(true)
@@ -77,19 +131,82 @@ fn main() -> Result<()> {
       .subcommand(
         Command::new("module")
             .about("upload and execute Module with data")
+            .args(runner_args())
             .arg(
-              arg!(parallel: -p --parallel "If set, the task will be executed concurrently")
+              arg!(data_file_path: -d --data [DATA_PATH] "sets the Data-path")
+                  .required(false)
             )
             .arg(
-              arg!(data_file_path: -d --data [DATA_PATH] "sets the Data-path")
+              arg!(template: -T --template "If set, the data file is a template with the current host as context data")
+            )
+            .arg(
+              arg!(module: -m --module [MODULE_PATH] "Path or name of the module that should run")
+                  .required_unless_present_any(&["list", "gc"])
+            )
+            .arg(
+              arg!(list: -l --list "List the modules found in TRICORDER_MODULE_PATH and the modules/ directory next to each inventory")
+            )
+            .arg(
+              arg!(args: -a --arg <KEY_VALUE> "Argument passed to the module as key=value, nested keys are separated by dots (can be repeated)")
+                  .required(false)
+                  .multiple_occurrences(true)
+            )
+            .arg(
+              arg!(data_json: --"data-json" <JSON> "JSON object passed to the module, merged on top of the data file and host variables")
                   .required(false)
             )
             .arg(
-              arg!(module: -m --module [MODULE_PATH] "Module that should run")
-                  .required(true)
+              arg!(verbose: -v --verbose "If set, the data sent to the module is included in the result")
+            )
+            .arg(
+              arg!(workspace: -w --workspace <DIR> "Remote workspace directory, modules are uploaded to its modules/ subdirectory (default: ~/.local/tricorder)")
+                  .required(false)
+            )
+            .arg(
+              arg!(cleanup: --cleanup "If set, the module is removed from the hosts after its execution")
+            )
+            .arg(
+              arg!(gc: --gc "List the modules uploaded on the hosts and remove the stale ones")
+            )
+            .arg(
+              arg!(max_age: --"max-age" <DAYS> "With --gc, number of days after which an unused module is stale (default: 30)")
+                  .required(false)
+            )
+            .arg(
+              arg!(dry_run: --"dry-run" "With --gc, only list the stale modules without removing them")
+            )
+            .arg(
+              arg!(force_upload: --"force-upload" "If set, upload the module even if an identical copy is already present on the host")
             )
       )
     .get_matches();
 
-    cli::run(matches)
+    if let Err(err) = cli::run(matches) {
+        eprintln!("Error: {}", err);
+        exit(1);
+    }
+}
+
+fn runner_args<'help>() -> Vec<Arg<'help>> {
+    vec![
+      arg!(parallel: -p --parallel "If set, the task will be executed concurrently"),
+      arg!(serial: --serial <COUNT> "Run the task batch by batch, on COUNT hosts (or COUNT% of the hosts) at a time")
+        .required(false),
+      arg!(pause: --pause <DURATION> "With --serial, duration to wait between two batches (example: 30s, 5m)")
+        .required(false),
+      arg!(batch_max_fail: --"batch-max-fail" <COUNT> "With --serial, skip the next batches once more than COUNT hosts (or COUNT% of the hosts) of a batch failed")
+        .required(false),
+      arg!(fail_fast: --"fail-fast" "If set, skip the remaining hosts as soon as a host failed")
+        .conflicts_with("max_fail"),
+      arg!(max_fail: --"max-fail" <COUNT> "Skip the remaining hosts once more than COUNT hosts (or COUNT% of the hosts) failed")
+        .required(false),
+      arg!(retries: --retries <N> "Number of times the task is retried on a host after a connection error")
+        .required(false),
+      arg!(retry_delay: --"retry-delay" <DURATION> "Duration to wait before the first retry, doubled after each attempt")
+        .required(false)
+        .default_value("1s"),
+      arg!(retry_on_exit_code: --"retry-on-exit-code" "If set, also retry the task when it returns a non-zero exit code"),
+      arg!(failed_file: --"failed-file" <PATH> "File where the ids of the failed hosts are written, and read by --rerun-failed (default: ~/.cache/tricorder/failed-<KEY>.txt, with a key per set of inventories)")
+        .required(false),
+    ]
 }
@@ -6,14 +6,26 @@ use std::{
 #[derive(Debug, Clone)]
 pub enum Error {
     MissingInput(String),
+    InvalidArgument(String),
     CommandExecutionFailed(String),
     UploadFailed(String),
     FileNotFound(String),
     IsADirectory(String),
     IsAbsolute(String),
+    InvalidInventory(String),
     InvalidHostId(String),
+    DuplicateHostId(String),
+    DuplicateHostGroup(String),
     InvalidHostTag(String),
+    InvalidHostPattern(String),
+    InvalidHostGroup(String),
+    InvalidHostRange(String),
     InvalidToken(String),
+    /// The host could not be reached, `category` telling at which step
+    HostUnreachable {
+        category: String,
+        reason: String,
+    },
     Other(String),
 }
 
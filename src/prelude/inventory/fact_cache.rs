@@ -0,0 +1,111 @@
+use super::{host_entry::Host, host_id::HostId};
+use crate::prelude::Result;
+
+use serde_json::{json, Value};
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// On-disk cache of the facts gathered on hosts.
+///
+/// Facts are stored as one JSON document per host in the cache directory, and
+/// are considered stale once they are older than the configured TTL.
+///
+/// Example:
+///
+/// ```rust
+/// use tricorder::prelude::*;
+/// use std::time::Duration;
+///
+/// let cache = FactCache::new(FactCache::default_dir(), Duration::from_secs(3600));
+/// let mut host = Host::new(Host::id("localhost").unwrap(), "localhost:22".to_string());
+///
+/// // populates `host.facts` if they were cached and are still fresh
+/// cache.load(&mut host).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FactCache {
+    /// Directory where the facts are stored
+    dir: PathBuf,
+    /// Duration after which cached facts are ignored
+    ttl: Duration,
+}
+
+impl FactCache {
+    /// Create a new fact cache
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        Self { dir, ttl }
+    }
+
+    /// Default cache directory: `$XDG_CACHE_HOME/tricorder/facts`, falling
+    /// back to `$HOME/.cache/tricorder/facts`.
+    pub fn default_dir() -> PathBuf {
+        let cache_dir = match env::var_os("XDG_CACHE_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => match env::var_os("HOME") {
+                Some(dir) => PathBuf::from(dir).join(".cache"),
+                None => env::temp_dir(),
+            },
+        };
+
+        cache_dir.join("tricorder").join("facts")
+    }
+
+    /// Get `Some(facts)` for a host, or `None` if they were never cached or
+    /// are older than the TTL.
+    pub fn get(&self, id: &HostId) -> Result<Option<HashMap<String, Value>>> {
+        let path = self.path_for(id);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let entry: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        let gathered_at = entry["gathered_at"].as_u64().unwrap_or(0);
+
+        if now()?.saturating_sub(gathered_at) > self.ttl.as_secs() {
+            return Ok(None);
+        }
+
+        let facts = serde_json::from_value(entry["facts"].clone())?;
+        Ok(Some(facts))
+    }
+
+    /// Store the facts of a host in the cache.
+    pub fn set(&self, id: &HostId, facts: &HashMap<String, Value>) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let entry = json!({
+          "host": id,
+          "gathered_at": now()?,
+          "facts": facts,
+        });
+        fs::write(self.path_for(id), serde_json::to_string_pretty(&entry)?)?;
+
+        Ok(())
+    }
+
+    /// Populate the host's facts from the cache. Returns `false` if no fresh
+    /// facts were found.
+    pub fn load(&self, host: &mut Host) -> Result<bool> {
+        match self.get(&host.id)? {
+            Some(facts) => {
+                host.set_facts(facts);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn path_for(&self, id: &HostId) -> PathBuf {
+        self.dir.join(format!("{}.json", id.clone().to_string()))
+    }
+}
+
+fn now() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
@@ -0,0 +1,73 @@
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::collections::HashMap;
+
+/// Inventory-wide settings, applied to the hosts which do not override them
+/// (either directly or via their groups).
+///
+/// Example:
+///
+/// ```toml
+/// [defaults]
+/// user = "admin"
+/// port = 2222
+/// connect_timeout = 10
+/// vars = { env = "prod" }
+///
+/// [[hosts]]
+/// id = "web-01"
+/// address = "10.0.1.10"
+/// ```
+///
+/// Here, `web-01` is resolved with the address `10.0.1.10:2222`, the user
+/// `admin`, a connection timeout of 10 seconds and the variables
+/// `{env = "prod"}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HostDefaults {
+    /// SSH user of the hosts which do not set their own (defaults to `root`)
+    #[serde(default)]
+    pub user: Option<String>,
+    /// SSH port of the hosts whose address has no port (defaults to `22`)
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Timeout (in seconds) to open the TCP connection and the SSH session to
+    /// the hosts which do not set their own
+    #[serde(default)]
+    pub connect_timeout: Option<u64>,
+    /// Variables of all hosts, unless they (or their groups) override them
+    /// (defaults to `{}`)
+    #[serde(default)]
+    pub vars: HashMap<String, Value>,
+}
+
+impl HostDefaults {
+    /// Create new empty defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the default user
+    pub fn set_user(&mut self, user: String) -> &mut Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Set the default port
+    pub fn set_port(&mut self, port: u16) -> &mut Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Set the default connection timeout, in seconds
+    pub fn set_connect_timeout(&mut self, connect_timeout: u64) -> &mut Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Set default variable
+    pub fn set_var(&mut self, key: String, val: Value) -> &mut Self {
+        self.vars.insert(key, val);
+        self
+    }
+}
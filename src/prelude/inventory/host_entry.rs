@@ -1,28 +1,46 @@
 use super::{host_id::HostId, host_tag::HostTag};
 use crate::prelude::Result;
 
+use serde::Serializer;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value;
 
 use ssh2::Session;
-use std::{collections::HashMap, net::TcpStream};
+use std::{
+    collections::HashMap,
+    io,
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
 
 /// Abstraction of a host found in the inventory
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Host {
     /// Host identifier
     pub id: HostId,
-    /// SSH host address in the form of `hostname:port`
+    /// SSH host address in the form of `hostname:port` (the port defaults to
+    /// the inventory's default port, or `22`)
     pub address: String,
-    /// SSH user to authenticate with (defaults to `root`)
-    #[serde(default = "default_user")]
-    pub user: String,
+    /// SSH user to authenticate with (defaults to the user of its groups, or
+    /// `root`, see `Host::user()`)
+    #[serde(default, serialize_with = "serialize_user")]
+    pub user: Option<String>,
     /// Tags used to apply commands on a subset of hosts from the inventory (defaults to `[]`)
     #[serde(default = "default_tags")]
     pub tags: Vec<HostTag>,
     /// Variables specific to this host, used by templates (defaults to `{}`)
     #[serde(default = "default_vars")]
     pub vars: HashMap<String, Value>,
+    /// Groups this host inherits settings from (defaults to `[]`)
+    #[serde(default = "default_groups")]
+    pub groups: Vec<String>,
+    /// Timeout (in seconds) to open the TCP connection and the SSH session to
+    /// this host (defaults to the inventory's default timeout, or none)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+    /// Facts gathered on this host, used by templates (defaults to `{}`)
+    #[serde(default = "default_facts", skip_deserializing)]
+    pub facts: HashMap<String, Value>,
 }
 
 impl Host {
@@ -41,15 +59,30 @@ impl Host {
         Self {
             id,
             address,
-            user: default_user(),
+            user: None,
             tags: default_tags(),
             vars: default_vars(),
+            groups: default_groups(),
+            connect_timeout: None,
+            facts: default_facts(),
         }
     }
 
     /// Override this host's user
     pub fn set_user(&mut self, user: String) -> &mut Self {
-        self.user = user;
+        self.user = Some(user);
+        self
+    }
+
+    /// SSH user to authenticate with, `root` if the host has none
+    pub fn user(&self) -> &str {
+        self.user.as_deref().unwrap_or(DEFAULT_USER)
+    }
+
+    /// Set the timeout (in seconds) to open the TCP connection and the SSH
+    /// session to this host
+    pub fn set_connect_timeout(&mut self, connect_timeout: u64) -> &mut Self {
+        self.connect_timeout = Some(connect_timeout);
         self
     }
 
@@ -77,21 +110,108 @@ impl Host {
         self
     }
 
+    /// Add this host to a group, whose settings are applied when the
+    /// inventory is loaded
+    pub fn add_group(&mut self, group: String) -> &mut Self {
+        self.groups.push(group);
+        self
+    }
+
+    /// Replace this host's facts
+    pub fn set_facts(&mut self, facts: HashMap<String, Value>) -> &mut Self {
+        self.facts = facts;
+        self
+    }
+
     /// Open SSH session to host and authenticate using `ssh-agent`
+    ///
+    /// The `connect_timeout` applies until the session is authenticated, the
+    /// commands run on the session have no timeout.
     pub fn get_session(&self) -> Result<Session> {
-        let sock = TcpStream::connect(self.address.clone())?;
+        let sock = self.connect()?;
+        let sess = self.handshake(sock)?;
+        self.authenticate(&sess)?;
+        sess.set_timeout(0);
+
+        Ok(sess)
+    }
+
+    /// Open TCP connection to host
+    pub fn connect(&self) -> Result<TcpStream> {
+        let sock = match self.connect_timeout {
+            Some(timeout) => {
+                let addr = self.address.to_socket_addrs()?.next().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::AddrNotAvailable,
+                        format!("No address found for {}", self.address),
+                    )
+                })?;
+                TcpStream::connect_timeout(&addr, Duration::from_secs(timeout))?
+            }
+            None => TcpStream::connect(self.address.clone())?,
+        };
+        Ok(sock)
+    }
+
+    /// Perform the SSH handshake on an opened TCP connection
+    ///
+    /// The blocking calls of the session time out after the host's
+    /// `connect_timeout`, if any, so that a host which accepts the connection
+    /// but never answers does not hang the handshake.
+    pub fn handshake(&self, sock: TcpStream) -> Result<Session> {
         let mut sess = Session::new()?;
 
+        if let Some(timeout) = self.connect_timeout {
+            let timeout_ms = timeout.saturating_mul(1000).min(u32::MAX as u64);
+            sess.set_timeout(timeout_ms as u32);
+        }
         sess.set_tcp_stream(sock);
         sess.handshake()?;
-        sess.userauth_agent(&self.user)?;
 
         Ok(sess)
     }
+
+    /// Authenticate the SSH session using `ssh-agent`
+    pub fn authenticate(&self, sess: &Session) -> Result<()> {
+        sess.userauth_agent(self.user())?;
+        Ok(())
+    }
+}
+
+/// Append the port to an address which has none (IPv6 addresses are put in
+/// brackets).
+pub(super) fn with_port(address: &str, port: u16) -> String {
+    if address.starts_with('[') {
+        if address.contains("]:") {
+            address.to_string()
+        } else {
+            format!("{}:{}", address, port)
+        }
+    } else {
+        match address.matches(':').count() {
+            0 => format!("{}:{}", address, port),
+            1 => address.to_string(),
+            _ => format!("[{}]:{}", address, port),
+        }
+    }
+}
+
+const DEFAULT_USER: &str = "root";
+
+pub(super) fn default_user() -> String {
+    String::from(DEFAULT_USER)
+}
+
+/// Serialize the user of a host as the one it authenticates with.
+fn serialize_user<S>(user: &Option<String>, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(user.as_deref().unwrap_or(DEFAULT_USER))
 }
 
-fn default_user() -> String {
-    String::from("root")
+pub(super) fn default_port() -> u16 {
+    22
 }
 
 fn default_tags() -> Vec<HostTag> {
@@ -101,3 +221,11 @@ fn default_tags() -> Vec<HostTag> {
 fn default_vars() -> HashMap<String, Value> {
     HashMap::new()
 }
+
+fn default_groups() -> Vec<String> {
+    vec![]
+}
+
+fn default_facts() -> HashMap<String, Value> {
+    HashMap::new()
+}
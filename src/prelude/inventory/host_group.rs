@@ -0,0 +1,100 @@
+use super::host_tag::HostTag;
+
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::collections::HashMap;
+
+/// Abstraction of a group of hosts found in the inventory, whose settings are
+/// inherited by the hosts joining it.
+///
+/// Example:
+///
+/// ```toml
+/// [groups.prod]
+/// vars = { env = "prod" }
+///
+/// [groups.web]
+/// groups = ["prod"]
+/// user = "admin"
+/// port = 2222
+/// tags = ["web"]
+/// vars = { http_port = 8080 }
+///
+/// [[hosts]]
+/// id = "web-01"
+/// address = "10.0.1.10"
+/// groups = ["web"]
+/// vars = { http_port = 8081 }
+/// ```
+///
+/// Here, `web-01` is resolved with the address `10.0.1.10:2222`, the user
+/// `admin`, the tag `web`, and the variables
+/// `{env = "prod", http_port = 8081}`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HostGroup {
+    /// SSH user of the hosts which do not set their own
+    #[serde(default)]
+    pub user: Option<String>,
+    /// SSH port of the hosts whose address has no port
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// Timeout (in seconds) to open the TCP connection and the SSH session to
+    /// the hosts which do not set their own
+    #[serde(default)]
+    pub connect_timeout: Option<u64>,
+    /// Tags added to the hosts of this group (defaults to `[]`)
+    #[serde(default)]
+    pub tags: Vec<HostTag>,
+    /// Variables of the hosts of this group, unless they override them
+    /// (defaults to `{}`)
+    #[serde(default)]
+    pub vars: HashMap<String, Value>,
+    /// Parent groups, whose settings are inherited by this group (defaults to
+    /// `[]`)
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+impl HostGroup {
+    /// Create a new empty group
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the user of the hosts of this group
+    pub fn set_user(&mut self, user: String) -> &mut Self {
+        self.user = Some(user);
+        self
+    }
+
+    /// Set the port of the hosts of this group
+    pub fn set_port(&mut self, port: u16) -> &mut Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Set the connection timeout of the hosts of this group, in seconds
+    pub fn set_connect_timeout(&mut self, connect_timeout: u64) -> &mut Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Add tag to the hosts of this group
+    pub fn add_tag(&mut self, tag: HostTag) -> &mut Self {
+        self.tags.push(tag);
+        self
+    }
+
+    /// Set group variable
+    pub fn set_var(&mut self, key: String, val: Value) -> &mut Self {
+        self.vars.insert(key, val);
+        self
+    }
+
+    /// Inherit the settings of a parent group
+    pub fn add_group(&mut self, group: String) -> &mut Self {
+        self.groups.push(group);
+        self
+    }
+}
@@ -0,0 +1,161 @@
+use super::host_id::HostId;
+use crate::prelude::{Error, Result};
+
+use regex::Regex;
+
+/// Pattern matching host identifiers.
+///
+/// A pattern is either:
+///
+///  - an exact host identifier: `web-01`
+///  - a glob pattern, with `*`, `?` and character classes: `web-*`, `db-0[1-3]`
+///  - a regular expression, prefixed with `~`: `~^web-\d+$`
+///
+/// Example:
+///
+/// ```rust
+/// use tricorder::prelude::{Host, HostPattern};
+///
+/// let pattern = HostPattern::new("db-0[1-3]").unwrap();
+/// assert!(pattern.is_match(&Host::id("db-02").unwrap()));
+/// assert!(!pattern.is_match(&Host::id("db-04").unwrap()));
+///
+/// let pattern = HostPattern::new("~^web-\\d+$").unwrap();
+/// assert!(pattern.is_match(&Host::id("web-42").unwrap()));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HostPattern {
+    src: String,
+    regex: Regex,
+}
+
+impl HostPattern {
+    /// Compile a host pattern.
+    pub fn new(src: &str) -> Result<Self> {
+        let src = src.trim();
+
+        if src.is_empty() {
+            return Err(Box::new(Error::InvalidHostPattern(String::from(
+                "Empty host pattern",
+            ))));
+        }
+
+        let regex_src = match src.strip_prefix('~') {
+            Some(regex_src) => regex_src.to_string(),
+            None => glob_to_regex(src).ok_or_else(|| {
+                Error::InvalidHostPattern(format!(
+                    "Invalid host pattern '{}': unclosed character class",
+                    src
+                ))
+            })?,
+        };
+        let regex = Regex::new(&regex_src).map_err(|err| {
+            Error::InvalidHostPattern(format!("Invalid host pattern '{}': {}", src, err))
+        })?;
+
+        Ok(Self {
+            src: src.to_string(),
+            regex,
+        })
+    }
+
+    /// Compile a comma-separated list of host patterns.
+    ///
+    /// Example: `web-*,db-0[1-3]`
+    ///
+    /// Commas inside brackets, braces or parentheses are part of the pattern,
+    /// as in `~^web-\d{1,3}$`.
+    pub fn parse_list(src: &str) -> Result<Vec<Self>> {
+        split_list(src)
+            .into_iter()
+            .filter(|pattern| !pattern.trim().is_empty())
+            .map(Self::new)
+            .collect()
+    }
+
+    /// Check if a host identifier matches the pattern.
+    pub fn is_match(&self, id: &HostId) -> bool {
+        self.regex.is_match(&id.clone().to_string())
+    }
+
+    /// Return the pattern as it was written
+    pub fn as_str(&self) -> &str {
+        &self.src
+    }
+}
+
+impl PartialEq for HostPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.src == other.src
+    }
+}
+
+/// Split a list of patterns on the commas which are not nested in brackets,
+/// braces or parentheses, nor escaped with a backslash.
+fn split_list(src: &str) -> Vec<&str> {
+    let mut patterns = vec![];
+    let mut depth = 0usize;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, c) in src.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' | '{' | '(' => depth += 1,
+            ']' | '}' | ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                patterns.push(&src[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    patterns.push(&src[start..]);
+    patterns
+}
+
+/// Translate a glob pattern into an anchored regular expression, or `None`
+/// if a character class is not closed.
+pub(super) fn glob_to_regex(src: &str) -> Option<String> {
+    let mut regex_src = String::from("^");
+    let mut chars = src.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex_src.push_str(".*"),
+            '?' => regex_src.push('.'),
+            '[' => {
+                regex_src.push('[');
+
+                let mut class = String::new();
+                let mut closed = false;
+
+                for c in chars.by_ref() {
+                    if c == ']' && !class.is_empty() {
+                        closed = true;
+                        break;
+                    }
+                    class.push(c);
+                }
+
+                if !closed {
+                    return None;
+                }
+
+                if let Some(negated) = class.strip_prefix('!') {
+                    regex_src.push('^');
+                    class = negated.to_string();
+                }
+
+                regex_src.push_str(&class.replace('\\', "\\\\"));
+                regex_src.push(']');
+            }
+            _ => regex_src.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    regex_src.push('$');
+    Some(regex_src)
+}
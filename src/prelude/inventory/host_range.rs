@@ -0,0 +1,209 @@
+use crate::prelude::{Error, Result};
+
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+
+const HOST_RANGE_REGEX: &str = r"\[(\d+):(\d+)\]";
+
+/// Maximum number of hosts generated by a single range
+const MAX_HOST_RANGE_LEN: u64 = 10_000;
+
+fn host_range_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(HOST_RANGE_REGEX).unwrap())
+}
+
+/// Numeric range found in a host's `id` or `address`, such as `web-[01:40]`.
+#[derive(Debug, Clone, PartialEq)]
+struct HostRange {
+    prefix: String,
+    suffix: String,
+    start: u64,
+    end: u64,
+    /// Zero-padding of the numbers, if the start of the range has leading zeros
+    width: usize,
+}
+
+impl HostRange {
+    /// Find the range in a string, `None` if there is none.
+    fn parse(src: &str) -> Result<Option<Self>> {
+        let mut matches = host_range_regex().captures_iter(src);
+
+        let caps = match matches.next() {
+            Some(caps) => caps,
+            None => return Ok(None),
+        };
+
+        if matches.next().is_some() {
+            return Err(Box::new(Error::InvalidHostRange(format!(
+                "Only one range is allowed in '{}'",
+                src
+            ))));
+        }
+
+        let whole = caps.get(0).unwrap();
+        let (start_src, end_src) = (&caps[1], &caps[2]);
+        let invalid_number =
+            |_| Error::InvalidHostRange(format!("Range bounds are too large in '{}'", src));
+        let start: u64 = start_src.parse().map_err(invalid_number)?;
+        let end: u64 = end_src.parse().map_err(invalid_number)?;
+
+        if start > end {
+            return Err(Box::new(Error::InvalidHostRange(format!(
+                "Range start is greater than its end in '{}'",
+                src
+            ))));
+        }
+
+        if end - start >= MAX_HOST_RANGE_LEN {
+            return Err(Box::new(Error::InvalidHostRange(format!(
+                "Range of '{}' has more than {} hosts",
+                src, MAX_HOST_RANGE_LEN
+            ))));
+        }
+
+        let width = if start_src.starts_with('0') {
+            start_src.len()
+        } else {
+            0
+        };
+
+        Ok(Some(Self {
+            prefix: src[..whole.start()].to_string(),
+            suffix: src[whole.end()..].to_string(),
+            start,
+            end,
+            width,
+        }))
+    }
+
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    fn nth(&self, n: u64) -> (u64, String) {
+        let index = self.start + n;
+        let value = format!(
+            "{}{:0width$}{}",
+            self.prefix,
+            index,
+            self.suffix,
+            width = self.width
+        );
+        (index, value)
+    }
+}
+
+/// Check if an inventory document may contain a host range, without parsing
+/// it.
+pub(super) fn has_host_ranges(content: &str) -> bool {
+    host_range_regex().is_match(content)
+}
+
+/// Expand the host entries of a raw inventory document whose `id` (and
+/// optionally `address`) contain a range, such as:
+///
+/// ```toml
+/// [[hosts]]
+/// id = "web-[01:40]"
+/// address = "10.0.1.[10:49]:22"
+/// ```
+///
+/// Each generated host gets the number from its `id` as the `range_index`
+/// variable.
+pub(super) fn expand_host_ranges(doc: &mut Value) -> Result<()> {
+    let hosts = match doc.get_mut("hosts").and_then(|hosts| hosts.as_array_mut()) {
+        Some(hosts) => hosts,
+        None => return Ok(()),
+    };
+
+    let mut expanded_hosts = vec![];
+
+    for host in hosts.drain(..) {
+        let id = host["id"].as_str().unwrap_or_default();
+        let address = host["address"].as_str().unwrap_or_default();
+
+        let id_range = HostRange::parse(id)?;
+        let address_range = HostRange::parse(address)?;
+
+        let id_range = match (id_range, &address_range) {
+            (Some(id_range), _) => id_range,
+            (None, None) => {
+                expanded_hosts.push(host);
+                continue;
+            }
+            (None, Some(_)) => {
+                return Err(Box::new(Error::InvalidHostRange(format!(
+                    "Address '{}' has a range but id '{}' does not",
+                    address, id
+                ))));
+            }
+        };
+
+        if let Some(address_range) = &address_range {
+            if address_range.len() != id_range.len() {
+                return Err(Box::new(Error::InvalidHostRange(format!(
+                    "Range of id '{}' has {} hosts but range of address '{}' has {}",
+                    id,
+                    id_range.len(),
+                    address,
+                    address_range.len()
+                ))));
+            }
+        }
+
+        for n in 0..id_range.len() {
+            let (index, id) = id_range.nth(n);
+            let mut expanded_host = host.clone();
+            expanded_host["id"] = json!(id);
+
+            if let Some(address_range) = &address_range {
+                expanded_host["address"] = json!(address_range.nth(n).1);
+            }
+
+            if !expanded_host["vars"].is_object() {
+                expanded_host["vars"] = json!({});
+            }
+            expanded_host["vars"]["range_index"] = json!(index);
+
+            expanded_hosts.push(expanded_host);
+        }
+    }
+
+    *hosts = expanded_hosts;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn host_range_should_parse_ranges() {
+        let range = HostRange::parse("web-[08:10].example.com")
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(range.len(), 3);
+        assert_eq!(range.nth(0), (8, String::from("web-08.example.com")));
+        assert_eq!(range.nth(2), (10, String::from("web-10.example.com")));
+
+        let range = HostRange::parse("10.0.1.[9:10]:22").unwrap().unwrap();
+        assert_eq!(range.nth(0), (9, String::from("10.0.1.9:22")));
+
+        assert_eq!(HostRange::parse("[::1]:22").unwrap(), None);
+        assert!(HostRange::parse("web-[10:01]").is_err());
+        assert!(HostRange::parse("rack-[1:2]-node-[1:4]").is_err());
+    }
+
+    #[test]
+    fn host_range_should_be_capped() {
+        let range = HostRange::parse("web-[1:10000]").unwrap().unwrap();
+        assert_eq!(range.len(), MAX_HOST_RANGE_LEN);
+
+        assert!(HostRange::parse("web-[1:10001]").is_err());
+        assert!(HostRange::parse("web-[0:18446744073709551615]").is_err());
+        assert!(HostRange::parse("web-[0:99999999999999999999]").is_err());
+    }
+}
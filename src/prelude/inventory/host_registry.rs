@@ -1,10 +1,26 @@
-use super::{host_entry::Host, host_id::HostId, tag_expr::eval_tag_expr};
+use super::{
+    host_defaults::HostDefaults,
+    host_entry::{default_port, default_user, with_port, Host},
+    host_group::HostGroup,
+    host_id::HostId,
+    host_pattern::HostPattern,
+    host_range::{expand_host_ranges, has_host_ranges},
+    tag_expr::TagExpr,
+};
 use crate::prelude::{Error, Result};
 
+use serde::{de, Deserialize as _};
 use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
 
 use is_executable::IsExecutable;
-use std::{fs, path::Path, process::Command};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 /// Abstraction of inventory file
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -12,12 +28,22 @@ pub struct Inventory {
     /// List of host provided by inventory (defaults to `[]`)
     #[serde(default = "default_hostlist")]
     pub hosts: Vec<Host>,
+    /// Groups of hosts, by name (defaults to `{}`)
+    #[serde(default)]
+    pub groups: BTreeMap<String, HostGroup>,
+    /// Settings applied to the hosts which do not override them
+    #[serde(default)]
+    pub defaults: HostDefaults,
 }
 
 impl Inventory {
     /// Create a new empty inventory
     pub fn new() -> Self {
-        Inventory { hosts: vec![] }
+        Inventory {
+            hosts: vec![],
+            groups: BTreeMap::new(),
+            defaults: HostDefaults::new(),
+        }
     }
 
     /// Deserialize a TOML document into an inventory.
@@ -25,6 +51,9 @@ impl Inventory {
     /// Example:
     ///
     /// ```toml
+    /// [defaults]
+    /// user = "admin"
+    ///
     /// [[hosts]]
     ///
     /// id = "localhost"
@@ -32,12 +61,43 @@ impl Inventory {
     /// user = "root"
     /// tags = ["local"]
     /// vars = { foo = "bar" }
+    /// groups = ["dev"]
+    ///
+    /// [groups.dev]
+    /// vars = { env = "dev" }
+    /// ```
+    ///
+    /// The defaults and the settings of the groups are applied to the hosts
+    /// (see `HostDefaults` and `HostGroup`).
+    ///
+    /// A host entry whose `id` contains a range generates one host per number
+    /// in the range. Its `address` can contain a range of the same length, and
+    /// each host gets its number as the `range_index` variable:
+    ///
+    /// ```toml
+    /// [[hosts]]
+    ///
+    /// id = "web-[01:40]"
+    /// address = "10.0.1.[10:49]:22"
     /// ```
+    ///
+    /// Syntax errors are reported with their line and column, and invalid
+    /// settings with the host, group or defaults they belong to.
     pub fn from_toml(content: &str) -> Result<Self> {
-        let inventory: Self = toml::from_str(content)?;
+        let mut inventory = Self::parse_toml(content)?;
+        inventory.resolve()?;
         Ok(inventory)
     }
 
+    /// Deserialize a TOML document, without resolving its hosts
+    fn parse_toml(content: &str) -> Result<Self> {
+        if has_host_ranges(content) {
+            Self::from_document(toml::from_str(content)?)
+        } else {
+            Ok(toml::from_str(content)?)
+        }
+    }
+
     /// Deserialize a JSON document into an inventory.
     ///
     /// Example:
@@ -49,42 +109,274 @@ impl Inventory {
     ///     "address": "localhost:22",
     ///     "user": "root",
     ///     "tags": ["local"],
-    ///     "vars": {"foo": "bar"}
+    ///     "vars": {"foo": "bar"},
+    ///     "groups": ["dev"]
     ///   }
-    /// ]}
+    /// ], "groups": {
+    ///   "dev": {"vars": {"env": "dev"}}
+    /// }, "defaults": {
+    ///   "user": "admin"
+    /// }}
     /// ```
+    ///
+    /// The defaults and the settings of the groups are applied to the hosts,
+    /// and host ranges are expanded, like in `Inventory::from_toml()`.
     pub fn from_json(content: &str) -> Result<Self> {
-        let inventory: Self = serde_json::from_str(content)?;
+        let mut inventory = Self::parse_json(content)?;
+        inventory.resolve()?;
         Ok(inventory)
     }
 
-    /// Parse inventory from a file or executable
+    /// Deserialize a JSON document, without resolving its hosts
+    fn parse_json(content: &str) -> Result<Self> {
+        if has_host_ranges(content) {
+            Self::from_document(serde_json::from_str(content)?)
+        } else {
+            Ok(serde_json::from_str(content)?)
+        }
+    }
+
+    /// Build an inventory from a parsed document, once its host ranges are
+    /// expanded.
+    ///
+    /// Since the document has no line and column anymore, its sections are
+    /// deserialized one by one, and an error names the section it comes from.
+    fn from_document(mut doc: Value) -> Result<Self> {
+        expand_host_ranges(&mut doc)?;
+
+        let mut inventory = Inventory::new();
+
+        if let Some(defaults) = doc.get("defaults") {
+            let defaults =
+                HostDefaults::deserialize(defaults).map_err(|err| locate_error(err, "defaults"))?;
+            inventory.set_defaults(defaults);
+        }
+
+        if let Some(groups) = doc.get("groups") {
+            let groups = BTreeMap::<String, Value>::deserialize(groups)
+                .map_err(|err| locate_error(err, "groups"))?;
+
+            for (name, group) in groups {
+                let group = HostGroup::deserialize(group)
+                    .map_err(|err| locate_error(err, &format!("group '{}'", name)))?;
+                inventory.add_group(name, group);
+            }
+        }
+
+        if let Some(hosts) = doc.get("hosts") {
+            let hosts =
+                Vec::<Value>::deserialize(hosts).map_err(|err| locate_error(err, "hosts"))?;
+
+            for (n, host) in hosts.into_iter().enumerate() {
+                let location = match host["id"].as_str() {
+                    Some(id) => format!("host '{}'", id),
+                    None => format!("host #{}", n + 1),
+                };
+                let host = Host::deserialize(host).map_err(|err| locate_error(err, &location))?;
+                inventory.add_host(host);
+            }
+        }
+
+        Ok(inventory)
+    }
+
+    /// Parse inventory from a file or executable.
+    ///
+    /// An executable must print a JSON inventory. A file is parsed as JSON if
+    /// its extension is `.json`, as TOML if its extension is `.toml`, and
+    /// otherwise as JSON only if its content starts with `{`.
     pub fn from_file(path: &str) -> Result<Self> {
+        let mut inventory = Self::read_file(path)?;
+        inventory.resolve()?;
+        Ok(inventory)
+    }
+
+    /// Parse an inventory file or executable, without resolving its hosts
+    fn read_file(path: &str) -> Result<Self> {
         let inventory_path = Path::new(path);
 
-        if inventory_path.exists() {
-            if inventory_path.is_executable() {
-                let result = Command::new(path).output()?;
-
-                if !result.status.success() {
-                    Err(Box::new(Error::CommandExecutionFailed(format!(
-                        "Failed to execute inventory {}: {}",
-                        path, result.status
-                    ))))
-                } else {
-                    let content = String::from_utf8(result.stdout)?;
-                    Ok(Inventory::from_json(&content)?)
-                }
-            } else {
-                let content = fs::read_to_string(path)?;
-                Ok(Inventory::from_toml(&content)?)
-            }
-        } else {
-            Err(Box::new(Error::FileNotFound(format!(
+        if !inventory_path.exists() {
+            return Err(Box::new(Error::FileNotFound(format!(
                 "Inventory '{}' does not exist",
                 path
-            ))))
+            ))));
+        }
+
+        let (format, content) = if inventory_path.is_executable() {
+            let result = Command::new(path).output()?;
+
+            if !result.status.success() {
+                return Err(Box::new(Error::CommandExecutionFailed(format!(
+                    "Failed to execute inventory {}: {}",
+                    path, result.status
+                ))));
+            }
+
+            (InventoryFormat::Json, String::from_utf8(result.stdout)?)
+        } else {
+            let content = fs::read_to_string(path)?;
+            (InventoryFormat::detect(inventory_path, &content), content)
+        };
+
+        let inventory = match format {
+            InventoryFormat::Toml => Inventory::parse_toml(&content),
+            InventoryFormat::Json => Inventory::parse_json(&content),
+        };
+
+        inventory.map_err(|err| {
+            Error::InvalidInventory(format!(
+                "Failed to parse inventory '{}' as {}: {}",
+                path,
+                format.name(),
+                err
+            ))
+            .into()
+        })
+    }
+
+    /// Parse inventory from several files, executables or directories, and
+    /// merge them.
+    ///
+    /// A directory provides every `*.toml` and `*.json` file and every
+    /// executable it contains, in sorted order. The hosts, groups and defaults
+    /// of all the files are merged before being resolved, so that a host can
+    /// join a group defined in another file.
+    ///
+    /// Two hosts with the same ID, two different groups with the same name, or
+    /// two different values of the same default, are rejected, naming the
+    /// files defining them.
+    pub fn from_paths(paths: &[&str]) -> Result<Self> {
+        let mut inventory = Inventory::new();
+        let mut sources: HashMap<String, String> = HashMap::new();
+        let mut group_sources: HashMap<String, String> = HashMap::new();
+        let mut default_sources: HashMap<String, String> = HashMap::new();
+
+        for path in paths {
+            for source in inventory_sources(Path::new(path))? {
+                let source = source.display().to_string();
+                let source_inventory = Inventory::read_file(&source)?;
+
+                for host in source_inventory.hosts {
+                    let host_id = host.id.clone().to_string();
+
+                    if let Some(previous_source) = sources.get(&host_id) {
+                        return Err(Box::new(Error::DuplicateHostId(format!(
+                            "Host '{}' is defined in both '{}' and '{}'",
+                            host_id, previous_source, source
+                        ))));
+                    }
+
+                    sources.insert(host_id, source.clone());
+                    inventory.add_host(host);
+                }
+
+                for (name, group) in source_inventory.groups {
+                    if let Some(previous_group) = inventory.groups.get(&name) {
+                        if *previous_group != group {
+                            return Err(Box::new(Error::DuplicateHostGroup(format!(
+                                "Group '{}' is defined differently in '{}' and '{}'",
+                                name, group_sources[&name], source
+                            ))));
+                        }
+                        continue;
+                    }
+
+                    group_sources.insert(name.clone(), source.clone());
+                    inventory.add_group(name, group);
+                }
+
+                let defaults = source_inventory.defaults;
+                let merged = &mut inventory.defaults;
+                let sources = &mut default_sources;
+
+                merge_default("user", &mut merged.user, defaults.user, &source, sources)?;
+                merge_default("port", &mut merged.port, defaults.port, &source, sources)?;
+                merge_default(
+                    "connect_timeout",
+                    &mut merged.connect_timeout,
+                    defaults.connect_timeout,
+                    &source,
+                    sources,
+                )?;
+
+                for (key, val) in defaults.vars {
+                    let mut current = merged.vars.remove(&key);
+                    let name = format!("vars.{}", key);
+                    merge_default(&name, &mut current, Some(val), &source, sources)?;
+                    merged.vars.extend(current.map(|val| (key, val)));
+                }
+            }
+        }
+
+        inventory.resolve()?;
+        Ok(inventory)
+    }
+
+    /// Add group to the inventory.
+    pub fn add_group(&mut self, name: String, group: HostGroup) -> &mut Self {
+        self.groups.insert(name, group);
+        self
+    }
+
+    /// Set the inventory-wide defaults.
+    pub fn set_defaults(&mut self, defaults: HostDefaults) -> &mut Self {
+        self.defaults = defaults;
+        self
+    }
+
+    /// Apply the defaults and the settings of the groups to the hosts.
+    ///
+    /// A host inherits the settings of its groups, and a group the settings of
+    /// its parent groups. The settings of a host override the ones of its
+    /// groups, and the settings of a group override the ones of its parents and
+    /// of the groups listed before it. The defaults apply to the settings that
+    /// neither the host nor its groups set.
+    pub fn resolve(&mut self) -> Result<&mut Self> {
+        for host in self.hosts.iter_mut() {
+            let mut group_names = vec![];
+
+            for name in host.groups.iter() {
+                let context = format!("Host '{}'", host.id.clone().to_string());
+                collect_groups(&self.groups, name, &context, &mut vec![], &mut group_names)?;
+            }
+
+            let mut user = self.defaults.user.clone();
+            let mut port = self.defaults.port;
+            let mut connect_timeout = self.defaults.connect_timeout;
+            let mut tags = vec![];
+            let mut vars = self.defaults.vars.clone();
+
+            for name in group_names {
+                let group = &self.groups[name];
+
+                user = group.user.clone().or(user);
+                port = group.port.or(port);
+                connect_timeout = group.connect_timeout.or(connect_timeout);
+                tags.extend(group.tags.iter().cloned());
+                vars.extend(group.vars.clone());
+            }
+
+            if host.user.is_none() {
+                host.user = Some(user.unwrap_or_else(default_user));
+            }
+
+            for tag in tags {
+                if !host.tags.contains(&tag) {
+                    host.tags.push(tag);
+                }
+            }
+
+            vars.extend(host.vars.drain());
+            host.vars = vars;
+
+            host.address = with_port(&host.address, port.unwrap_or_else(default_port));
+
+            if host.connect_timeout.is_none() {
+                host.connect_timeout = connect_timeout;
+            }
         }
+
+        Ok(self)
     }
 
     /// Add host to the inventory.
@@ -108,25 +400,154 @@ impl Inventory {
             .map(|host| host.clone())
     }
 
+    /// Get a list of host whose ID matches at least one of the patterns.
+    pub fn get_hosts_by_patterns(&self, patterns: &[HostPattern]) -> Vec<Host> {
+        self.hosts
+            .iter()
+            .filter(|host| patterns.iter().any(|pattern| pattern.is_match(&host.id)))
+            .cloned()
+            .collect()
+    }
+
     /// Get a list of host matching the tag expression.
+    ///
+    /// Besides tags, the expression can test the host's ID (`id:db-*`) and
+    /// variables (`env=prod`, `vars.region~"eu-*"`, `facts.os.id=debian`), see
+    /// `TagExpr` for the full syntax.
     pub fn get_hosts_by_tags(&self, tag_expr: String) -> Result<Vec<Host>> {
-        let mut hosts = vec![];
-
-        for host in self.hosts.iter() {
-            let tags = host
-                .tags
-                .iter()
-                .map(|tag| tag.clone().to_string())
-                .collect();
-            if eval_tag_expr(&tag_expr, tags)? {
-                hosts.push(host.clone());
-            }
+        let expr = TagExpr::parse(&tag_expr)?;
+        Ok(self.get_hosts_by_tag_expr(&expr))
+    }
+
+    /// Get a list of host matching the compiled tag expression.
+    pub fn get_hosts_by_tag_expr(&self, expr: &TagExpr) -> Vec<Host> {
+        self.hosts
+            .iter()
+            .filter(|host| expr.matches(host))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Format of an inventory file
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InventoryFormat {
+    Toml,
+    Json,
+}
+
+impl InventoryFormat {
+    /// Guess the format of a file from its extension, or from its content if
+    /// the extension is unknown.
+    fn detect(path: &Path, content: &str) -> Self {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("json") => InventoryFormat::Json,
+            Some("toml") => InventoryFormat::Toml,
+            _ if content.trim_start().starts_with('{') => InventoryFormat::Json,
+            _ => InventoryFormat::Toml,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            InventoryFormat::Toml => "TOML",
+            InventoryFormat::Json => "JSON",
+        }
+    }
+}
+
+/// List the inventory files of a path: the path itself if it is a file, or
+/// the `*.toml` and `*.json` files and the executables of a directory.
+fn inventory_sources(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut sources = vec![];
+
+    for entry in fs::read_dir(path)? {
+        let source = entry?.path();
+        let is_inventory_file = matches!(
+            source.extension().and_then(OsStr::to_str),
+            Some("toml") | Some("json")
+        );
+
+        if source.is_file() && (is_inventory_file || source.is_executable()) {
+            sources.push(source);
         }
+    }
+
+    sources.sort();
+    Ok(sources)
+}
+
+/// Merge a default setting of an inventory `source` into the merged one,
+/// unless another inventory already set it to a different value.
+fn merge_default<T: PartialEq>(
+    name: &str,
+    current: &mut Option<T>,
+    value: Option<T>,
+    source: &str,
+    sources: &mut HashMap<String, String>,
+) -> Result<()> {
+    let value = match value {
+        Some(value) => value,
+        None => return Ok(()),
+    };
 
-        Ok(hosts)
+    match current {
+        Some(current) if *current != value => Err(Box::new(Error::InvalidInventory(format!(
+            "Default '{}' is defined differently in '{}' and '{}'",
+            name, sources[name], source
+        )))),
+        Some(_) => Ok(()),
+        None => {
+            sources.insert(name.to_string(), source.to_string());
+            *current = Some(value);
+            Ok(())
+        }
     }
 }
 
+/// Name the section of an inventory document an error comes from
+fn locate_error(err: serde_json::Error, location: &str) -> serde_json::Error {
+    de::Error::custom(format!("{} in {}", err, location))
+}
+
 fn default_hostlist() -> Vec<Host> {
     vec![]
 }
+
+/// Collect the names of a group and of its ancestors, ancestors first.
+fn collect_groups<'a>(
+    groups: &'a BTreeMap<String, HostGroup>,
+    name: &'a str,
+    context: &str,
+    path: &mut Vec<&'a str>,
+    group_names: &mut Vec<&'a str>,
+) -> Result<()> {
+    let group = groups.get(name).ok_or_else(|| {
+        Error::InvalidHostGroup(format!("{} joins undefined group '{}'", context, name))
+    })?;
+
+    if path.contains(&name) {
+        path.push(name);
+        return Err(Box::new(Error::InvalidHostGroup(format!(
+            "Cycle in the groups: {}",
+            path.join(" -> ")
+        ))));
+    }
+
+    path.push(name);
+    let context = format!("Group '{}'", name);
+    for parent in group.groups.iter() {
+        collect_groups(groups, parent, &context, path, group_names)?;
+    }
+    path.pop();
+
+    if !group_names.contains(&name) {
+        group_names.push(name);
+    }
+
+    Ok(())
+}
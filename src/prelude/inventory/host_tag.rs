@@ -36,6 +36,11 @@ impl HostTag {
         let Self(s) = self;
         s
     }
+
+    /// Return the underlying string, without copying it
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
 impl<'de> Deserialize<'de> for HostTag {
@@ -1,7 +1,22 @@
+mod fact_cache;
+mod host_defaults;
 mod host_entry;
+mod host_group;
 mod host_id;
+mod host_pattern;
+mod host_range;
 mod host_registry;
 mod host_tag;
 mod tag_expr;
 
-pub use self::{host_entry::Host, host_id::HostId, host_registry::Inventory, host_tag::HostTag};
+pub use self::{
+    fact_cache::FactCache,
+    host_defaults::HostDefaults,
+    host_entry::Host,
+    host_group::HostGroup,
+    host_id::HostId,
+    host_pattern::HostPattern,
+    host_registry::Inventory,
+    host_tag::HostTag,
+    tag_expr::{TagExpr, TagExprError},
+};
@@ -1,6 +1,22 @@
+//! Boolean expressions selecting hosts.
+
+use super::{
+    host_entry::Host,
+    host_pattern::{glob_to_regex, HostPattern},
+    host_tag::HostTag,
+};
 use crate::prelude::{Error, Result};
 use bet::BeTree;
 use logos::Logos;
+use regex::Regex;
+use serde_json::Value;
+
+use std::{
+    error::Error as BaseError,
+    fmt::{self, Display, Formatter},
+    ops::Not,
+    str::FromStr,
+};
 
 #[derive(Logos, Debug, PartialEq)]
 enum Token {
@@ -19,7 +35,7 @@ enum Token {
     #[token("!")]
     NotOp,
 
-    #[regex(r"[^!\&\|\t\n\r\f\(\) ]+", |lex| lex.slice().parse())]
+    #[regex(r#"([^!\&\|\t\n\r\f\(\) "]|"[^"]*")+"#, |lex| lex.slice().parse())]
     Tag(String),
 
     #[error]
@@ -34,69 +50,626 @@ enum BoolOp {
     Not,
 }
 
-fn parse(input: &str) -> Result<BeTree<BoolOp, String>> {
+#[derive(Debug, Clone)]
+enum Atom {
+    /// The host has the tag
+    Tag(String),
+    /// The host has a tag matching the glob pattern
+    TagGlob(Regex),
+    /// The host's ID matches the pattern
+    Id(HostPattern),
+    /// The variable at the path is equal to the value
+    Equals(Vec<String>, String),
+    /// The variable at the path matches the glob pattern
+    Matches(Vec<String>, Regex),
+}
+
+impl Atom {
+    /// Parse an atom, or return the reason why it is invalid.
+    fn parse(src: &str) -> std::result::Result<Self, String> {
+        let segments = split_quoted(src)?;
+
+        if let Some((first, false)) = segments.first() {
+            if let Some(pattern) = first.strip_prefix("id:") {
+                let mut pattern_segments = vec![(pattern.to_string(), false)];
+                pattern_segments.extend(segments[1..].iter().cloned());
+
+                let pattern =
+                    HostPattern::new(&join_segments(&pattern_segments)).map_err(|err| match err
+                        .downcast_ref::<Error>(
+                    ) {
+                        Some(Error::InvalidHostPattern(reason)) => reason.clone(),
+                        _ => err.to_string(),
+                    })?;
+                return Ok(Self::Id(pattern));
+            }
+        }
+
+        match split_at_operator(&segments) {
+            Some((path, '=', value)) => {
+                let path = parse_path(&join_segments(&path))?;
+                Ok(Self::Equals(path, join_segments(&value)))
+            }
+            Some((path, _, value)) => {
+                let path = parse_path(&join_segments(&path))?;
+                Ok(Self::Matches(path, compile_glob(&join_segments(&value))?))
+            }
+            None => {
+                let tag = join_segments(&segments);
+                let literal = matches!(segments.as_slice(), [(_, true)]);
+
+                if !literal && tag.contains(['*', '?', '[']) {
+                    Ok(Self::TagGlob(compile_glob(&tag)?))
+                } else {
+                    Ok(Self::Tag(tag))
+                }
+            }
+        }
+    }
+
+    fn eval(&self, host: &Host) -> bool {
+        match self {
+            Self::Tag(tag) => host.tags.iter().any(|t| t.as_str() == tag),
+            Self::TagGlob(regex) => host.tags.iter().any(|t| regex.is_match(t.as_str())),
+            Self::Id(pattern) => pattern.is_match(&host.id),
+            Self::Equals(path, value) => lookup(host, path).is_some_and(|val| val == *value),
+            Self::Matches(path, regex) => {
+                lookup(host, path).is_some_and(|val| regex.is_match(&val))
+            }
+        }
+    }
+}
+
+fn compile_glob(pattern: &str) -> std::result::Result<Regex, String> {
+    glob_to_regex(pattern)
+        .and_then(|regex_src| Regex::new(&regex_src).ok())
+        .ok_or_else(|| format!("invalid glob pattern '{}'", pattern))
+}
+
+/// Parse a variable path, as a list of keys starting with `vars` or `facts`.
+fn parse_path(src: &str) -> std::result::Result<Vec<String>, String> {
+    let mut path: Vec<String> = src.split('.').map(String::from).collect();
+
+    if path[0] != "vars" && path[0] != "facts" {
+        path.insert(0, String::from("vars"));
+    }
+
+    if path.len() < 2 || path.iter().any(|key| key.is_empty()) {
+        return Err(format!("invalid variable path '{}'", src));
+    }
+
+    Ok(path)
+}
+
+/// Get the value at the path as a string, `None` if it is missing or `null`.
+fn lookup(host: &Host, path: &[String]) -> Option<String> {
+    let root = if path[0] == "facts" {
+        &host.facts
+    } else {
+        &host.vars
+    };
+
+    let mut val = root.get(&path[1])?;
+    for key in path[2..].iter() {
+        val = val.get(key)?;
+    }
+
+    match val {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        _ => Some(val.to_string()),
+    }
+}
+
+/// Part of an atom, with `true` if it was quoted.
+type Segment = (String, bool);
+
+/// Split an atom into its unquoted and quoted parts, without the quotes.
+///
+/// Example: `region~"eu *"` is split into `region~` and `eu *` (quoted).
+fn split_quoted(src: &str) -> std::result::Result<Vec<Segment>, String> {
+    let mut segments = vec![];
+    let mut rest = src;
+
+    while !rest.is_empty() {
+        match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted
+                    .find('"')
+                    .ok_or_else(|| format!("unclosed quote in '{}'", src))?;
+                segments.push((quoted[..end].to_string(), true));
+                rest = &quoted[end + 1..];
+            }
+            None => {
+                let end = rest.find('"').unwrap_or(rest.len());
+                segments.push((rest[..end].to_string(), false));
+                rest = &rest[end..];
+            }
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Split the segments of an atom around its first unquoted `=` or `~`.
+fn split_at_operator(segments: &[Segment]) -> Option<(Vec<Segment>, char, Vec<Segment>)> {
+    for (i, (text, quoted)) in segments.iter().enumerate() {
+        if *quoted {
+            continue;
+        }
+
+        if let Some(pos) = text.find(['=', '~']) {
+            let mut left = segments[..i].to_vec();
+            left.push((text[..pos].to_string(), false));
+
+            let mut right = vec![(text[pos + 1..].to_string(), false)];
+            right.extend(segments[i + 1..].iter().cloned());
+
+            return Some((left, text[pos..].chars().next().unwrap(), right));
+        }
+    }
+
+    None
+}
+
+fn join_segments(segments: &[Segment]) -> String {
+    segments.iter().map(|(text, _)| text.as_str()).collect()
+}
+
+/// Error returned when a tag expression is malformed.
+///
+/// When displayed, the expression is rendered with a caret under the
+/// offending character:
+///
+/// ```text
+/// Invalid tag expression, expected a tag after '&':
+///   foo &
+///        ^
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagExprError {
+    /// The malformed expression
+    pub expr: String,
+    /// Offset (in characters) of the offending spot in the expression
+    pub offset: usize,
+    /// Why the expression is malformed
+    pub reason: String,
+}
+
+impl TagExprError {
+    fn new(expr: &str, byte_offset: usize, reason: String) -> Self {
+        Self {
+            expr: expr.to_string(),
+            offset: expr[..byte_offset].chars().count(),
+            reason,
+        }
+    }
+}
+
+impl Display for TagExprError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Invalid tag expression, {}:\n  {}\n  {}^",
+            self.reason,
+            self.expr,
+            " ".repeat(self.offset)
+        )
+    }
+}
+
+impl BaseError for TagExprError {}
+
+fn parse(input: &str) -> Result<Node> {
     let mut expr = BeTree::new();
-    let lex = Token::lexer(input);
+    let lex = Token::lexer(input).spanned();
+
+    let error = |offset: usize, reason: String| TagExprError::new(input, offset, reason);
+
+    // offsets of the parentheses not yet closed
+    let mut open_pars: Vec<usize> = vec![];
+    // true if the next token must be a tag, a `!` or a `(`
+    let mut expect_operand = true;
+    // the last operator, to report dangling ones
+    let mut last_operator: Option<&str> = None;
+
+    for (tok, span) in lex {
+        let slice = &input[span.clone()];
 
-    for tok in lex {
         match tok {
-            Token::OpenParen => expr.open_par(),
-            Token::CloseParen => expr.close_par(),
-            Token::AndOp => expr.push_operator(BoolOp::And),
-            Token::OrOp => expr.push_operator(BoolOp::Or),
-            Token::NotOp => expr.push_operator(BoolOp::Not),
-            Token::Tag(tag) => expr.push_atom(tag),
-            _ => {
-                return Err(Box::new(Error::InvalidToken(format!(
-                    "Invalid token in tag expression: {:?}",
-                    tok
-                ))));
+            Token::Tag(_) | Token::NotOp | Token::OpenParen if !expect_operand => {
+                return Err(Box::new(error(
+                    span.start,
+                    format!("expected an operator before '{}'", slice),
+                )));
+            }
+            Token::AndOp | Token::OrOp | Token::CloseParen if expect_operand => {
+                let reason = match last_operator {
+                    Some(op) => format!("expected a tag after '{}', found '{}'", op, slice),
+                    None if tok == Token::CloseParen && !open_pars.is_empty() => {
+                        String::from("empty parentheses")
+                    }
+                    None => format!("expected a tag before '{}'", slice),
+                };
+                return Err(Box::new(error(span.start, reason)));
+            }
+            Token::CloseParen if open_pars.is_empty() => {
+                return Err(Box::new(error(span.start, String::from("unbalanced ')'"))));
             }
+            Token::Error => {
+                return Err(Box::new(error(
+                    span.start,
+                    format!("unexpected character '{}'", slice),
+                )));
+            }
+            _ => {}
+        }
+
+        expect_operand = !matches!(tok, Token::CloseParen | Token::Tag(_));
+
+        match tok {
+            Token::OpenParen => {
+                open_pars.push(span.start);
+                last_operator = None;
+                expr.open_par();
+            }
+            Token::CloseParen => {
+                open_pars.pop();
+                expr.close_par();
+            }
+            Token::AndOp => {
+                last_operator = Some("&");
+                expr.push_operator(BoolOp::And);
+            }
+            Token::OrOp => {
+                last_operator = Some("|");
+                expr.push_operator(BoolOp::Or);
+            }
+            Token::NotOp => {
+                last_operator = Some("!");
+                expr.push_operator(BoolOp::Not);
+            }
+            Token::Tag(atom) => {
+                let atom = Atom::parse(&atom).map_err(|reason| error(span.start, reason))?;
+                last_operator = None;
+                expr.push_atom(atom);
+            }
+            Token::Error => unreachable!("Invalid tokens are reported above"),
         }
     }
 
-    Ok(expr)
-}
+    let end = input.trim_end().len();
+
+    if let (true, Some(op)) = (expect_operand, last_operator) {
+        return Err(Box::new(error(
+            end,
+            format!("expected a tag after '{}'", op),
+        )));
+    }
+
+    if let Some(offset) = open_pars.pop() {
+        return Err(Box::new(error(offset, String::from("unbalanced '('"))));
+    }
 
-/// Evaluate a boolean tag expression against a list of tags.
-pub fn eval_tag_expr(expr: &str, tags: Vec<String>) -> Result<bool> {
-    let expr = parse(expr)?;
-    let res = expr.eval_faillible(
-        // evaluate leafs
-        |tag| Ok(tags.contains(tag)),
-        // evaluate operators
+    if expect_operand {
+        return Err(Box::new(error(end, String::from("empty expression"))));
+    }
+
+    let node = expr.eval(
+        |atom| Node::Atom(atom.clone()),
         |op, a, b| match (op, b) {
-            (BoolOp::And, Some(b)) => Ok(a & b),
-            (BoolOp::Or, Some(b)) => Ok(a | b),
-            (BoolOp::Not, None) => Ok(!a),
-            _ => Err("unexpected operation"),
-        },
-        // short-circuit
-        |op, a| match (op, a) {
-            (BoolOp::And, false) => true,
-            (BoolOp::Or, true) => true,
-            _ => false,
+            (BoolOp::And, Some(b)) => Node::And(Box::new(a), Box::new(b)),
+            (BoolOp::Or, Some(b)) => Node::Or(Box::new(a), Box::new(b)),
+            (_, _) => Node::Not(Box::new(a)),
         },
-    )?;
+        |_, _| false,
+    );
+
+    match node {
+        Some(node) => Ok(node),
+        None => unreachable!("Empty expressions are reported above"),
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Atom(Atom),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn eval(&self, host: &Host) -> bool {
+        match self {
+            Self::Atom(atom) => atom.eval(host),
+            Self::Not(a) => !a.eval(host),
+            Self::And(a, b) => a.eval(host) && b.eval(host),
+            Self::Or(a, b) => a.eval(host) || b.eval(host),
+        }
+    }
+}
+
+/// Compiled boolean expression selecting hosts, parsed once and evaluated
+/// against many hosts.
+///
+/// An expression combines atoms with the operators `!`, `&` and `|`, and
+/// parentheses. An atom is one of:
+///
+/// | Atom | Matches the hosts... |
+/// | --- | --- |
+/// | `web` | with the tag `web` |
+/// | `dc:*` | with a tag matching the glob pattern (`*`, `?` and `[...]`, negated with `[^...]`) |
+/// | `id:db-*` | whose ID matches the pattern (see `HostPattern`) |
+/// | `env=prod` | whose variable `env` is equal to `prod` |
+/// | `vars.region~"eu-*"` | whose variable `region` matches the glob pattern |
+/// | `facts.os.id=debian` | whose cached fact `os.id` is equal to `debian` |
+///
+/// Variables are looked up in `host.vars`, unless the path starts with
+/// `facts.`. Values can be quoted to contain spaces or operators, and a
+/// quoted `id:`, `=` or `~` is matched literally. A fully quoted tag, like
+/// `"a[1]"`, is never read as a glob pattern.
+///
+/// Expressions can also be built programmatically.
+///
+/// Example:
+///
+/// ```rust
+/// use tricorder::prelude::*;
+/// use serde_json::json;
+///
+/// let host = Host::new(Host::id("web-01").unwrap(), "web-01:22".to_string())
+///   .add_tag(Host::tag("web").unwrap())
+///   .set_var("env".to_string(), json!("prod"))
+///   .to_owned();
+///
+/// let expr: TagExpr = "web & env=prod & !id:db-*".parse().unwrap();
+/// assert!(expr.matches(&host));
+///
+/// // the same expression, built programmatically
+/// let expr = TagExpr::tag(Host::tag("web").unwrap())
+///   .and(TagExpr::var_eq("env", "prod").unwrap())
+///   .and(!TagExpr::id(HostPattern::new("db-*").unwrap()));
+/// assert!(expr.matches(&host));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TagExpr(Node);
+
+impl TagExpr {
+    /// Compile a tag expression, or return a `TagExprError` if it is
+    /// malformed.
+    pub fn parse(src: &str) -> Result<Self> {
+        Ok(Self(parse(src)?))
+    }
+
+    /// Match the hosts with the tag
+    pub fn tag(tag: HostTag) -> Self {
+        Self(Node::Atom(Atom::Tag(tag.to_string())))
+    }
+
+    /// Match the hosts with a tag matching the glob pattern
+    pub fn tag_glob(pattern: &str) -> Result<Self> {
+        let regex = compile_glob(pattern).map_err(Error::InvalidArgument)?;
+        Ok(Self(Node::Atom(Atom::TagGlob(regex))))
+    }
+
+    /// Match the hosts whose ID matches the pattern
+    pub fn id(pattern: HostPattern) -> Self {
+        Self(Node::Atom(Atom::Id(pattern)))
+    }
+
+    /// Match the hosts whose variable at `path` (example: `region` or
+    /// `facts.os.id`) is equal to `value`
+    pub fn var_eq(path: &str, value: &str) -> Result<Self> {
+        let path = parse_path(path).map_err(Error::InvalidArgument)?;
+        Ok(Self(Node::Atom(Atom::Equals(path, value.to_string()))))
+    }
+
+    /// Match the hosts whose variable at `path` matches the glob pattern
+    pub fn var_matches(path: &str, pattern: &str) -> Result<Self> {
+        let path = parse_path(path).map_err(Error::InvalidArgument)?;
+        let regex = compile_glob(pattern).map_err(Error::InvalidArgument)?;
+        Ok(Self(Node::Atom(Atom::Matches(path, regex))))
+    }
+
+    /// Match the hosts matched by both expressions
+    pub fn and(self, other: Self) -> Self {
+        Self(Node::And(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// Match the hosts matched by either expression
+    pub fn or(self, other: Self) -> Self {
+        Self(Node::Or(Box::new(self.0), Box::new(other.0)))
+    }
 
-    match res {
-        Some(val) => Ok(val),
-        None => unreachable!("No boolean were returned"),
+    /// Check if the host is matched by the expression
+    pub fn matches(&self, host: &Host) -> bool {
+        self.0.eval(host)
+    }
+}
+
+impl Not for TagExpr {
+    type Output = Self;
+
+    /// Match the hosts not matched by the expression
+    fn not(self) -> Self {
+        Self(Node::Not(Box::new(self.0)))
+    }
+}
+
+impl FromStr for TagExpr {
+    type Err = Box<dyn BaseError + Send + Sync>;
+
+    fn from_str(src: &str) -> Result<Self> {
+        Self::parse(src)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_json::json;
+
+    fn eval_tag_expr(expr: &str, host: &Host) -> Result<bool> {
+        Ok(TagExpr::parse(expr)?.matches(host))
+    }
+
+    fn setup_host() -> Host {
+        Host::new(Host::id("db-01").unwrap(), "db-01:22".to_string())
+            .add_tag(Host::tag("foo").unwrap())
+            .add_tag(Host::tag("bar").unwrap())
+            .add_tag(Host::tag("baz").unwrap())
+            .set_var("env".to_string(), json!("prod"))
+            .set_var("region".to_string(), json!("eu west 1"))
+            .set_var("ssh".to_string(), json!({"port": 22}))
+            .to_owned()
+    }
 
     #[test]
     fn eval_tag_expr_should_return_appropriate_values() {
-        let tags = vec!["foo".to_string(), "bar".to_string(), "baz".to_string()];
+        let host = setup_host();
+
+        assert!(eval_tag_expr("foo", &host).unwrap());
+        assert!(eval_tag_expr("foo | biz", &host).unwrap());
+        assert!(!eval_tag_expr("foo & biz", &host).unwrap());
+        assert!(eval_tag_expr("foo & (bar | biz)", &host).unwrap());
+        assert!(!eval_tag_expr("foo & !(bar | biz)", &host).unwrap());
+    }
+
+    #[test]
+    fn eval_tag_expr_should_evaluate_predicates() {
+        let host = setup_host();
+
+        assert!(eval_tag_expr("env=prod", &host).unwrap());
+        assert!(eval_tag_expr("vars.env=prod", &host).unwrap());
+        assert!(!eval_tag_expr("env=staging", &host).unwrap());
+        assert!(!eval_tag_expr("missing=prod", &host).unwrap());
+        assert!(eval_tag_expr(r#"region~"eu *""#, &host).unwrap());
+        assert!(eval_tag_expr("ssh.port=22", &host).unwrap());
+        assert!(eval_tag_expr("id:db-*", &host).unwrap());
+        assert!(!eval_tag_expr(r#"env=prod & vars.region~"eu-*" & !id:db-*"#, &host).unwrap());
+        assert!(eval_tag_expr("foo & env=prod & !id:web-*", &host).unwrap());
+        assert!(!eval_tag_expr("facts.os.id=debian", &host).unwrap());
+    }
+
+    #[test]
+    fn eval_tag_expr_should_match_glob_tags() {
+        let host = Host::new(Host::id("web-01").unwrap(), "web-01:22".to_string())
+            .add_tag(Host::tag("dc:paris").unwrap())
+            .add_tag(Host::tag("role-web1").unwrap())
+            .to_owned();
+
+        assert!(eval_tag_expr("dc:*", &host).unwrap());
+        assert!(eval_tag_expr("role-web?", &host).unwrap());
+        assert!(eval_tag_expr("dc:[lp]*", &host).unwrap());
+        assert!(!eval_tag_expr("dc:lyon*", &host).unwrap());
+        assert!(!eval_tag_expr("role-web", &host).unwrap());
+        assert!(!eval_tag_expr("role-web??", &host).unwrap());
+
+        // `!` binds tighter than `&`, which binds tighter than `|`
+        assert!(!eval_tag_expr("!dc:* | role-db?", &host).unwrap());
+        assert!(eval_tag_expr("!dc:lyon* & role-web?", &host).unwrap());
+        assert!(eval_tag_expr("dc:lyon* & role-db? | role-web?", &host).unwrap());
+        assert!(!eval_tag_expr("dc:lyon* & (role-db? | role-web?)", &host).unwrap());
+        assert!(eval_tag_expr("!(dc:lyon* | role-db?) & dc:p*", &host).unwrap());
+        // `!` is an operator, character classes are negated with `^` instead
+        assert!(eval_tag_expr("dc:[^l]*", &host).unwrap());
+    }
+
+    fn parse_error(expr: &str) -> (usize, String) {
+        let err = parse(expr).expect_err("expression should be invalid");
+        let err = err
+            .downcast_ref::<TagExprError>()
+            .expect("error should be a TagExprError");
+        (err.offset, err.reason.clone())
+    }
+
+    #[test]
+    fn parse_should_report_errors_with_offset() {
+        assert_eq!(parse_error(""), (0, String::from("empty expression")));
+        assert_eq!(parse_error("  "), (0, String::from("empty expression")));
+        assert_eq!(
+            parse_error("foo &"),
+            (5, String::from("expected a tag after '&'"))
+        );
+        assert_eq!(
+            parse_error("foo & | bar"),
+            (6, String::from("expected a tag after '&', found '|'"))
+        );
+        assert_eq!(
+            parse_error("| foo"),
+            (0, String::from("expected a tag before '|'"))
+        );
+        assert_eq!(
+            parse_error("foo !bar"),
+            (4, String::from("expected an operator before '!'"))
+        );
+        assert_eq!(
+            parse_error("foo bar"),
+            (4, String::from("expected an operator before 'bar'"))
+        );
+        assert_eq!(parse_error("(foo"), (0, String::from("unbalanced '('")));
+        assert_eq!(parse_error("foo & ("), (6, String::from("unbalanced '('")));
+        assert_eq!(
+            parse_error("foo & (bar |"),
+            (12, String::from("expected a tag after '|'"))
+        );
+        assert_eq!(parse_error("foo)"), (3, String::from("unbalanced ')'")));
+        assert_eq!(
+            parse_error("foo & ()"),
+            (7, String::from("empty parentheses"))
+        );
+        assert_eq!(
+            parse_error("é & dc:[a"),
+            (4, String::from("invalid glob pattern 'dc:[a'"))
+        );
+    }
+
+    #[test]
+    fn tag_expr_error_should_render_a_caret() {
+        let err = TagExprError::new("foo & | bar", 6, String::from("expected a tag"));
+
+        assert_eq!(
+            err.to_string(),
+            "Invalid tag expression, expected a tag:\n  foo & | bar\n        ^"
+        );
+    }
+
+    #[test]
+    fn eval_tag_expr_should_fail_on_invalid_predicates() {
+        let host = setup_host();
+
+        assert!(eval_tag_expr("=prod", &host).is_err());
+        assert!(eval_tag_expr("vars=prod", &host).is_err());
+        assert!(eval_tag_expr("region~eu-[1", &host).is_err());
+        assert!(eval_tag_expr("id:db-[1", &host).is_err());
+        assert!(eval_tag_expr("dc:[paris", &host).is_err());
+        assert!(eval_tag_expr(r#"region~"eu"#, &host).is_err());
+    }
+
+    #[test]
+    fn eval_tag_expr_should_parse_quoted_parts() {
+        let host = Host::new(Host::id("web-01").unwrap(), "web-01:22".to_string())
+            .add_tag(Host::tag("dc:paris").unwrap())
+            .add_tag(Host::tag("id:web").unwrap())
+            .add_tag(Host::tag("motd=a=b").unwrap())
+            .add_tag(Host::tag("a[1]").unwrap())
+            .set_var("motd".to_string(), json!("a=b"))
+            .set_var("name".to_string(), json!("web-01-1"))
+            .to_owned();
 
-        assert!(eval_tag_expr("foo", tags.clone()).unwrap());
-        assert!(eval_tag_expr("foo | biz", tags.clone()).unwrap());
-        assert!(!eval_tag_expr("foo & biz", tags.clone()).unwrap());
-        assert!(eval_tag_expr("foo & (bar | biz)", tags.clone()).unwrap());
-        assert!(!eval_tag_expr("foo & !(bar | biz)", tags.clone()).unwrap());
+        assert!(eval_tag_expr(r#"motd="a=b""#, &host).unwrap());
+        assert!(eval_tag_expr(r#"motd=a"=b""#, &host).unwrap());
+        assert!(eval_tag_expr(r#"name~"web*"-?"#, &host).unwrap());
+        assert!(eval_tag_expr(r#""dc:paris""#, &host).unwrap());
+        assert!(eval_tag_expr(r#""dc:"*"#, &host).unwrap());
+        // a quoted `id:` prefix or operator is part of the tag
+        assert!(eval_tag_expr(r#""id:web""#, &host).unwrap());
+        assert!(!eval_tag_expr(r#""id:web-01""#, &host).unwrap());
+        assert!(eval_tag_expr(r#""motd=a=b""#, &host).unwrap());
+        assert!(!eval_tag_expr(r#""motd=a""#, &host).unwrap());
+        // a fully quoted tag is never a glob pattern
+        assert!(eval_tag_expr(r#""a[1]""#, &host).unwrap());
+        assert!(!eval_tag_expr(r#""a[0-9]""#, &host).unwrap());
     }
 }
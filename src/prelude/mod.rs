@@ -46,9 +46,12 @@
 //!   .to_owned();
 //! ```
 
+mod digest;
 mod error;
 mod inventory;
 mod result;
 mod tasks;
 
 pub use self::{error::Error, inventory::*, result::Result, tasks::*};
+
+pub(crate) use self::digest::sha256_hex;
@@ -1,7 +1,11 @@
+mod run_options;
 mod task;
 mod task_runner;
 
 pub use self::{
+    run_options::{HostCount, RunOptions},
     task::{GenericTask, TaskResult},
     task_runner::TaskRunner,
 };
+
+pub(crate) use self::task_runner::is_failed;
@@ -0,0 +1,185 @@
+use crate::prelude::{Error, Result};
+
+use std::{str::FromStr, time::Duration};
+
+/// A number of hosts, either absolute or relative to the number of hosts the
+/// task is run on.
+///
+/// Example:
+///
+/// ```rust
+/// use tricorder::prelude::HostCount;
+///
+/// let count: HostCount = "20%".parse().unwrap();
+/// assert_eq!(count, HostCount::Percent(20));
+/// assert_eq!(count.of(12), 3);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HostCount {
+    /// Absolute number of hosts
+    Absolute(usize),
+    /// Percentage of the hosts
+    Percent(u32),
+}
+
+impl HostCount {
+    /// Resolve the number of hosts among `total` hosts, rounded up
+    pub fn of(&self, total: usize) -> usize {
+        match *self {
+            Self::Absolute(count) => count,
+            Self::Percent(percent) => (total * percent as usize).div_ceil(100),
+        }
+    }
+
+    /// Check if `count` hosts among `total` hosts is above this threshold
+    pub fn is_exceeded_by(&self, count: usize, total: usize) -> bool {
+        match *self {
+            Self::Absolute(max) => count > max,
+            Self::Percent(percent) => count * 100 > total * percent as usize,
+        }
+    }
+}
+
+impl FromStr for HostCount {
+    type Err = Box<dyn std::error::Error + Send + Sync>;
+
+    fn from_str(src: &str) -> Result<Self> {
+        let invalid = || {
+            Box::new(Error::InvalidArgument(format!(
+                "Invalid host count '{}', expected a number or a percentage (example: 5 or 20%)",
+                src
+            )))
+        };
+
+        match src.trim().strip_suffix('%') {
+            Some(percent) => {
+                let percent = percent.trim().parse().map_err(|_| invalid())?;
+                Ok(Self::Percent(percent))
+            }
+            None => {
+                let count = src.trim().parse().map_err(|_| invalid())?;
+                Ok(Self::Absolute(count))
+            }
+        }
+    }
+}
+
+/// Options controlling how `TaskRunner::run_task_with()` runs a task on
+/// multiple hosts.
+///
+/// Example:
+///
+/// ```rust
+/// use tricorder::prelude::*;
+/// use std::time::Duration;
+///
+/// // run on 5 hosts at a time, waiting 30 seconds between each batch, and
+/// // stop if more than 20% of the hosts of a batch failed, and retry up to 3
+/// // times on connection errors, waiting 2s, 4s, then 8s
+/// let options = RunOptions::new()
+///   .set_parallel(true)
+///   .set_batch_size(HostCount::Absolute(5))
+///   .set_batch_pause(Duration::from_secs(30))
+///   .set_batch_max_failures(HostCount::Percent(20))
+///   .set_retries(3)
+///   .set_retry_delay(Duration::from_secs(2))
+///   .to_owned();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunOptions {
+    /// If true, the task is run concurrently on the hosts of a batch
+    pub parallel: bool,
+    /// Number of hosts per batch, all hosts are in a single batch if not set
+    pub batch_size: Option<HostCount>,
+    /// Duration to wait between two batches
+    pub batch_pause: Option<Duration>,
+    /// Maximum number of failed hosts in a batch before the next batches are
+    /// skipped
+    pub batch_max_failures: Option<HostCount>,
+    /// Maximum number of failed hosts, among all hosts, before the remaining
+    /// hosts are skipped
+    pub max_failures: Option<HostCount>,
+    /// Number of times the task is retried on a host after a transient failure
+    pub retries: u32,
+    /// Duration to wait before the first retry, doubled after each attempt
+    pub retry_delay: Duration,
+    /// If true, a non-zero exit code is considered a transient failure
+    pub retry_on_exit_code: bool,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            parallel: false,
+            batch_size: None,
+            batch_pause: None,
+            batch_max_failures: None,
+            max_failures: None,
+            retries: 0,
+            retry_delay: Duration::from_secs(1),
+            retry_on_exit_code: false,
+        }
+    }
+}
+
+impl RunOptions {
+    /// Create default options: a single batch, run sequentially, without
+    /// retries
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run the task concurrently on the hosts of a batch
+    pub fn set_parallel(&mut self, parallel: bool) -> &mut Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Run the task batch by batch
+    pub fn set_batch_size(&mut self, batch_size: HostCount) -> &mut Self {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Wait between two batches
+    pub fn set_batch_pause(&mut self, batch_pause: Duration) -> &mut Self {
+        self.batch_pause = Some(batch_pause);
+        self
+    }
+
+    /// Skip the next batches once a batch has more failed hosts than allowed
+    pub fn set_batch_max_failures(&mut self, batch_max_failures: HostCount) -> &mut Self {
+        self.batch_max_failures = Some(batch_max_failures);
+        self
+    }
+
+    /// Skip the remaining hosts once more hosts than allowed have failed
+    pub fn set_max_failures(&mut self, max_failures: HostCount) -> &mut Self {
+        self.max_failures = Some(max_failures);
+        self
+    }
+
+    /// Skip the remaining hosts as soon as a host has failed
+    pub fn set_fail_fast(&mut self) -> &mut Self {
+        self.set_max_failures(HostCount::Absolute(0))
+    }
+
+    /// Retry the task on a host after a transient failure (connection
+    /// errors, by default)
+    pub fn set_retries(&mut self, retries: u32) -> &mut Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait before the first retry, the delay is doubled after each attempt
+    pub fn set_retry_delay(&mut self, retry_delay: Duration) -> &mut Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Also retry the task when it returns a non-zero exit code
+    pub fn set_retry_on_exit_code(&mut self, retry_on_exit_code: bool) -> &mut Self {
+        self.retry_on_exit_code = retry_on_exit_code;
+        self
+    }
+}
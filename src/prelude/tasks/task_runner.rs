@@ -1,9 +1,39 @@
-use super::task::{GenericTask, TaskResult};
-use crate::prelude::{Host, Result};
+use super::{
+    run_options::RunOptions,
+    task::{GenericTask, TaskResult},
+};
+use crate::prelude::{Error, Host, Result};
 
 use rayon::prelude::*;
 use serde_json::{json, Value};
 
+use std::{
+    error::Error as BaseError,
+    io,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+// Session error codes of libssh2 (see `libssh2.h`) after which the
+// connection can be attempted again.
+
+/// The server banner could not be received
+const LIBSSH2_ERROR_BANNER_RECV: i32 = -2;
+/// The client banner could not be sent
+const LIBSSH2_ERROR_BANNER_SEND: i32 = -3;
+/// The key exchange with the server failed
+const LIBSSH2_ERROR_KEX_FAILURE: i32 = -5;
+/// Writing to the socket failed
+const LIBSSH2_ERROR_SOCKET_SEND: i32 = -7;
+/// A blocking operation timed out
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+/// The server closed the connection
+const LIBSSH2_ERROR_SOCKET_DISCONNECT: i32 = -13;
+/// The socket timed out while waiting for data
+const LIBSSH2_ERROR_SOCKET_TIMEOUT: i32 = -30;
+/// Reading from the socket failed
+const LIBSSH2_ERROR_SOCKET_RECV: i32 = -43;
+
 /// TaskRunner trait to extend the `Vec<Host>` type.
 pub trait TaskRunner {
     /// Helper function to run a task on multiple hosts either sequentially or
@@ -27,6 +57,34 @@ pub trait TaskRunner {
     /// Once the task is prepared for all hosts, this function calls the `apply()`
     /// method with the contextual data produce at the previous step.
     fn run_task_parallel<Data: Send>(&self, task: &dyn GenericTask<Data>) -> TaskResult;
+
+    /// Run a task on multiple hosts, batch by batch, as configured by the
+    /// options.
+    ///
+    /// This function first calls the `prepare()` method for all hosts. All should
+    /// succeed, or else the error is returned.
+    ///
+    /// Then, for each batch, this function calls the `apply()` method with the
+    /// contextual data produced at the previous step, sequentially or
+    /// concurrently. Once a batch has more failed hosts than allowed, or once
+    /// more hosts than allowed have failed overall, the remaining hosts are
+    /// skipped and reported as:
+    ///
+    /// ```json
+    /// {"host": "example-0", "success": false, "skipped": true}
+    /// ```
+    ///
+    /// A host has failed if the task returned an error, or if its result has
+    /// a non-zero `exit_code` or a `failed` flag set to `true`.
+    ///
+    /// If retries are enabled, the task is prepared and applied again on a host
+    /// after a transient failure, and the number of attempts is added to the
+    /// host's result as `"attempts"`.
+    fn run_task_with<Data: Send>(
+        &self,
+        task: &dyn GenericTask<Data>,
+        options: &RunOptions,
+    ) -> TaskResult;
 }
 
 impl TaskRunner for Vec<Host> {
@@ -61,6 +119,184 @@ impl TaskRunner for Vec<Host> {
 
         Ok(json!(results))
     }
+
+    fn run_task_with<Data: Send>(
+        &self,
+        task: &dyn GenericTask<Data>,
+        options: &RunOptions,
+    ) -> TaskResult {
+        let prepared = if options.parallel {
+            self.into_par_iter()
+                .map(|host| prepare_host(task, host))
+                .collect::<Result<Vec<(&Host, Data)>>>()?
+        } else {
+            self.iter()
+                .map(|host| prepare_host(task, host))
+                .collect::<Result<Vec<(&Host, Data)>>>()?
+        };
+
+        let batch_size = match options.batch_size {
+            Some(batch_size) => batch_size.of(self.len()).max(1),
+            None => self.len().max(1),
+        };
+
+        // shared between the threads, to stop as soon as possible
+        let failures = AtomicUsize::new(0);
+        let max_failures_exceeded = || {
+            options.max_failures.is_some_and(|max_failures| {
+                max_failures.is_exceeded_by(failures.load(Ordering::SeqCst), self.len())
+            })
+        };
+        let run_host = |(host, data): (&Host, Data)| {
+            if max_failures_exceeded() {
+                return skip_host(host);
+            }
+
+            let result = apply_with_retries(task, host, data, options);
+            if is_failed(&result) {
+                failures.fetch_add(1, Ordering::SeqCst);
+            }
+            result
+        };
+
+        let mut results: Vec<Value> = vec![];
+        let mut remaining = prepared.into_iter();
+        let mut stopped = false;
+
+        loop {
+            let batch: Vec<(&Host, Data)> = remaining.by_ref().take(batch_size).collect();
+
+            if batch.is_empty() {
+                break;
+            }
+
+            if stopped {
+                results.extend(batch.into_iter().map(|(host, _)| skip_host(host)));
+                continue;
+            }
+
+            if let (false, Some(pause)) = (results.is_empty(), options.batch_pause) {
+                thread::sleep(pause);
+            }
+
+            let batch_len = batch.len();
+            let batch_results: Vec<Value> = if options.parallel {
+                batch.into_par_iter().map(run_host).collect()
+            } else {
+                batch.into_iter().map(run_host).collect()
+            };
+
+            let batch_failures = batch_results
+                .iter()
+                .filter(|result| is_failed(result) && result["skipped"] != json!(true))
+                .count();
+
+            if let Some(max_failures) = options.batch_max_failures {
+                stopped = max_failures.is_exceeded_by(batch_failures, batch_len);
+            }
+            stopped = stopped || max_failures_exceeded();
+
+            results.extend(batch_results);
+        }
+
+        Ok(json!(results))
+    }
+}
+
+/// Check if a host's result, as returned by a `TaskRunner`, is a failure: the
+/// task returned an error, or its result has a non-zero `exit_code` or a
+/// `failed` flag set to `true`.
+pub(crate) fn is_failed(result: &Value) -> bool {
+    let info = &result["info"];
+
+    result["success"] != json!(true)
+        || info["failed"] == json!(true)
+        || info["exit_code"].as_i64().is_some_and(|code| code != 0)
+}
+
+/// Check if an error returned by a task is likely transient: a connection
+/// error, or a failed SSH handshake.
+fn is_transient_error(err: &(dyn BaseError + Send + Sync + 'static)) -> bool {
+    if let Some(err) = err.downcast_ref::<io::Error>() {
+        return matches!(
+            err.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::UnexpectedEof
+                | io::ErrorKind::HostUnreachable
+                | io::ErrorKind::NetworkUnreachable
+        );
+    }
+
+    if let Some(err) = err.downcast_ref::<ssh2::Error>() {
+        return matches!(
+            err.code(),
+            ssh2::ErrorCode::Session(
+                LIBSSH2_ERROR_BANNER_RECV
+                    | LIBSSH2_ERROR_BANNER_SEND
+                    | LIBSSH2_ERROR_KEX_FAILURE
+                    | LIBSSH2_ERROR_SOCKET_SEND
+                    | LIBSSH2_ERROR_TIMEOUT
+                    | LIBSSH2_ERROR_SOCKET_DISCONNECT
+                    | LIBSSH2_ERROR_SOCKET_TIMEOUT
+                    | LIBSSH2_ERROR_SOCKET_RECV
+            )
+        );
+    }
+
+    if let Some(Error::HostUnreachable { category, .. }) = err.downcast_ref::<Error>() {
+        return category != "authentication";
+    }
+
+    false
+}
+
+fn should_retry(result: &TaskResult, retry_on_exit_code: bool) -> bool {
+    match result {
+        Err(err) => is_transient_error(err.as_ref()),
+        Ok(info) => retry_on_exit_code && info["exit_code"].as_i64().is_some_and(|code| code != 0),
+    }
+}
+
+fn apply_with_retries<Data: Send>(
+    task: &dyn GenericTask<Data>,
+    host: &Host,
+    data: Data,
+    options: &RunOptions,
+) -> Value {
+    let mut result = task.apply(host.clone(), data);
+    let mut attempts = 1;
+    let mut delay = options.retry_delay;
+
+    while attempts <= options.retries && should_retry(&result, options.retry_on_exit_code) {
+        thread::sleep(delay);
+        delay *= 2;
+        attempts += 1;
+
+        result = task
+            .prepare(host.clone())
+            .and_then(|data| task.apply(host.clone(), data));
+    }
+
+    let mut output = host_result(host, result);
+
+    if options.retries > 0 {
+        output["attempts"] = json!(attempts);
+    }
+
+    output
+}
+
+fn skip_host(host: &Host) -> Value {
+    json!({
+      "host": host.id,
+      "success": false,
+      "skipped": true,
+    })
 }
 
 fn prepare_host<'host, Data: Send>(
@@ -76,13 +312,23 @@ fn apply_to_host<'host, Data: Send>(
     host: &'host Host,
     data: Data,
 ) -> Value {
-    task.apply(host.clone(), data).map_or_else(
+    host_result(host, task.apply(host.clone(), data))
+}
+
+fn host_result(host: &Host, result: TaskResult) -> Value {
+    result.map_or_else(
         |err| {
-            json!({
+            let mut res = json!({
               "host": host.id,
               "success": false,
               "error": format!("{}", err),
-            })
+            });
+
+            if let Some(Error::HostUnreachable { category, .. }) = err.downcast_ref::<Error>() {
+                res["category"] = json!(category);
+            }
+
+            res
         },
         |info| {
             json!({
@@ -97,7 +343,9 @@ fn apply_to_host<'host, Data: Send>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::prelude::Error;
+    use crate::prelude::HostCount;
+
+    use std::time::Duration;
 
     pub struct DummyTask;
 
@@ -125,6 +373,19 @@ mod tests {
         }
     }
 
+    /// Task whose preparation always succeeds, to test the `apply()` step
+    pub struct ApplyFailureTask;
+
+    impl GenericTask<i32> for ApplyFailureTask {
+        fn prepare(&self, _host: Host) -> Result<i32> {
+            Ok(0)
+        }
+
+        fn apply(&self, host: Host, data: i32) -> TaskResult {
+            DummyTask::new().apply(host, data)
+        }
+    }
+
     fn setup_success_host() -> Host {
         Host::new(Host::id("success").unwrap(), "success:22".to_string())
     }
@@ -162,6 +423,205 @@ mod tests {
         }
     }
 
+    #[test]
+    fn run_task_with_should_skip_batches_after_failures() {
+        let hosts = vec![
+            setup_success_host(),
+            setup_failure_host(),
+            setup_success_host(),
+            setup_success_host(),
+        ];
+        let task = ApplyFailureTask {};
+        let options = RunOptions::new()
+            .set_batch_size(HostCount::Absolute(2))
+            .set_batch_max_failures(HostCount::Percent(0))
+            .to_owned();
+
+        assert_eq!(
+            hosts.run_task_with(&task, &options).unwrap(),
+            json!([
+              {"host": "success", "success": true, "info": 1},
+              {"host": "failure", "success": false, "error": "Other(\"failure\")"},
+              {"host": "success", "success": false, "skipped": true},
+              {"host": "success", "success": false, "skipped": true},
+            ])
+        );
+
+        let options = RunOptions::new()
+            .set_parallel(true)
+            .set_batch_size(HostCount::Absolute(2))
+            .set_batch_max_failures(HostCount::Percent(50))
+            .to_owned();
+        let results = hosts.run_task_with(&task, &options).unwrap();
+
+        assert_eq!(results.as_array().unwrap().len(), 4);
+        assert_eq!(
+            results[3],
+            json!({"host": "success", "success": true, "info": 1})
+        );
+    }
+
+    #[test]
+    fn run_task_with_should_skip_hosts_after_max_failures() {
+        let hosts = vec![
+            setup_success_host(),
+            setup_failure_host(),
+            setup_success_host(),
+            setup_failure_host(),
+            setup_success_host(),
+        ];
+        let task = ApplyFailureTask {};
+        let options = RunOptions::new().set_fail_fast().to_owned();
+
+        assert_eq!(
+            hosts.run_task_with(&task, &options).unwrap(),
+            json!([
+              {"host": "success", "success": true, "info": 1},
+              {"host": "failure", "success": false, "error": "Other(\"failure\")"},
+              {"host": "success", "success": false, "skipped": true},
+              {"host": "failure", "success": false, "skipped": true},
+              {"host": "success", "success": false, "skipped": true},
+            ])
+        );
+
+        let options = RunOptions::new()
+            .set_batch_size(HostCount::Absolute(2))
+            .set_max_failures(HostCount::Percent(20))
+            .to_owned();
+        let results = hosts.run_task_with(&task, &options).unwrap();
+
+        assert_eq!(
+            results[2],
+            json!({"host": "success", "success": true, "info": 1})
+        );
+        assert_eq!(
+            results[4],
+            json!({"host": "success", "success": false, "skipped": true})
+        );
+    }
+
+    /// The server rejected the credentials
+    const LIBSSH2_ERROR_AUTHENTICATION_FAILED: i32 = -18;
+
+    /// Task failing on its first attempts with a connection error
+    pub struct FlakyTask {
+        failures: AtomicUsize,
+    }
+
+    impl GenericTask<i32> for FlakyTask {
+        fn prepare(&self, _host: Host) -> Result<i32> {
+            Ok(0)
+        }
+
+        fn apply(&self, _host: Host, data: i32) -> TaskResult {
+            if self.failures.fetch_sub(1, Ordering::SeqCst) > 0 {
+                Err(Box::new(io::Error::from(io::ErrorKind::ConnectionReset)))
+            } else {
+                Ok(json!(data + 1))
+            }
+        }
+    }
+
+    /// Task failing with an SSH authentication error
+    pub struct AuthFailureTask;
+
+    impl GenericTask<i32> for AuthFailureTask {
+        fn prepare(&self, _host: Host) -> Result<i32> {
+            Ok(0)
+        }
+
+        fn apply(&self, _host: Host, _data: i32) -> TaskResult {
+            Err(Box::new(ssh2::Error::new(
+                ssh2::ErrorCode::Session(LIBSSH2_ERROR_AUTHENTICATION_FAILED),
+                "authentication failed",
+            )))
+        }
+    }
+
+    #[test]
+    fn run_task_with_should_retry_transient_failures() {
+        let hosts = vec![setup_success_host()];
+        let options = RunOptions::new()
+            .set_retries(2)
+            .set_retry_delay(Duration::from_millis(1))
+            .to_owned();
+
+        let task = FlakyTask {
+            failures: AtomicUsize::new(2),
+        };
+        assert_eq!(
+            hosts.run_task_with(&task, &options).unwrap(),
+            json!([{"host": "success", "success": true, "info": 1, "attempts": 3}])
+        );
+
+        let task = FlakyTask {
+            failures: AtomicUsize::new(3),
+        };
+        let results = hosts.run_task_with(&task, &options).unwrap();
+        assert_eq!(results[0]["success"], json!(false));
+        assert_eq!(results[0]["attempts"], json!(3));
+
+        // non-transient errors are not retried
+        let task = ApplyFailureTask {};
+        let results = vec![setup_failure_host()]
+            .run_task_with(&task, &options)
+            .unwrap();
+        assert_eq!(results[0]["attempts"], json!(1));
+
+        let task = AuthFailureTask {};
+        let results = hosts.run_task_with(&task, &options).unwrap();
+        assert_eq!(results[0]["success"], json!(false));
+        assert_eq!(results[0]["attempts"], json!(1));
+    }
+
+    #[test]
+    fn is_transient_error_should_not_match_permanent_errors() {
+        let transient: Vec<Box<dyn BaseError + Send + Sync>> = vec![
+            Box::new(io::Error::from(io::ErrorKind::TimedOut)),
+            Box::new(ssh2::Error::new(
+                ssh2::ErrorCode::Session(LIBSSH2_ERROR_SOCKET_DISCONNECT),
+                "disconnected",
+            )),
+            Box::new(Error::HostUnreachable {
+                category: String::from("timeout"),
+                reason: String::from("timed out"),
+            }),
+        ];
+        for err in transient {
+            assert!(is_transient_error(err.as_ref()), "{:?}", err);
+        }
+
+        let permanent: Vec<Box<dyn BaseError + Send + Sync>> = vec![
+            Box::new(io::Error::from(io::ErrorKind::PermissionDenied)),
+            Box::new(ssh2::Error::new(
+                ssh2::ErrorCode::Session(LIBSSH2_ERROR_AUTHENTICATION_FAILED),
+                "authentication failed",
+            )),
+            Box::new(Error::HostUnreachable {
+                category: String::from("authentication"),
+                reason: String::from("authentication failed"),
+            }),
+            Box::new(Error::Other(String::from("failure"))),
+        ];
+        for err in permanent {
+            assert!(!is_transient_error(err.as_ref()), "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn is_failed_should_work() {
+        assert!(!is_failed(
+            &json!({"success": true, "info": {"exit_code": 0}})
+        ));
+        assert!(is_failed(
+            &json!({"success": true, "info": {"exit_code": 1}})
+        ));
+        assert!(is_failed(
+            &json!({"success": true, "info": {"failed": true}})
+        ));
+        assert!(is_failed(&json!({"success": false, "error": "..."})));
+    }
+
     #[test]
     fn apply_to_host_should_work() {
         let success_host = setup_success_host();
@@ -33,30 +33,59 @@
 //!       "address": "localhost:22",
 //!       "user": "root",
 //!       "tags": ["local"],
-//!       "vars": {"msg": "hello"}
+//!       "vars": {"msg": "hello"},
+//!       "facts": {}
 //!     }
 //!   }
 //! ]
 //! ```
 //!
-//! > **NB:** In the future, will be gathered facts like:
-//! >   - network interfaces
-//! >   - hostname and FQDN
-//! >   - OS / Kernel version
-//! >   - partitions / mount points
-//! >   - ...
+//! If facts gathering is enabled (see `Task::new_gather_facts()`), the task
+//! connects to the host and fills the `facts` object:
+//!
+//! ```json
+//! {
+//!   "hostname": "localhost",
+//!   "fqdn": "localhost.localdomain",
+//!   "kernel": {"name": "Linux", "release": "5.15.0", "machine": "x86_64"},
+//!   "os": {"id": "debian", "version_id": "11", "name": "Debian GNU/Linux"}
+//! }
+//! ```
+//!
+//! When a `FactCache` is provided, the gathered facts are stored in it so that
+//! other tasks can use them in their templates (`{host.facts.os.id}`).
 
 use crate::prelude::*;
 
-use serde_json::json;
+use serde_json::{json, Value};
+use ssh2::Session;
+
+use std::{collections::HashMap, io::prelude::*};
 
 /// Describe an `info` task
-pub struct Task;
+pub struct Task {
+    /// If true, connect to the host to gather facts
+    gather_facts: bool,
+    /// Where to store the gathered facts
+    fact_cache: Option<FactCache>,
+}
 
 impl Task {
     /// Create a new `info` task
     pub fn new() -> Self {
-        Self {}
+        Self {
+            gather_facts: false,
+            fact_cache: None,
+        }
+    }
+
+    /// Create a new `info` task gathering facts on the host, and storing them
+    /// in the cache if provided
+    pub fn new_gather_facts(fact_cache: Option<FactCache>) -> Self {
+        Self {
+            gather_facts: true,
+            fact_cache,
+        }
     }
 }
 
@@ -66,6 +95,93 @@ impl GenericTask<()> for Task {
     }
 
     fn apply(&self, host: Host, _data: ()) -> TaskResult {
+        let mut host = host;
+
+        if self.gather_facts {
+            let facts = gather_facts(&host)?;
+
+            if let Some(cache) = &self.fact_cache {
+                cache.set(&host.id, &facts)?;
+            }
+
+            host.set_facts(facts);
+        }
+
         Ok(json!(host))
     }
 }
+
+fn gather_facts(host: &Host) -> Result<HashMap<String, Value>> {
+    let sess = host.get_session()?;
+
+    let mut facts = HashMap::new();
+    facts.insert("hostname".to_string(), json!(run(&sess, "hostname")?));
+    facts.insert("fqdn".to_string(), json!(run(&sess, "hostname -f")?));
+    facts.insert(
+        "kernel".to_string(),
+        json!({
+          "name": run(&sess, "uname -s")?,
+          "release": run(&sess, "uname -r")?,
+          "machine": run(&sess, "uname -m")?,
+        }),
+    );
+    facts.insert(
+        "os".to_string(),
+        parse_os_release(&run(&sess, "cat /etc/os-release")?),
+    );
+
+    Ok(facts)
+}
+
+fn run(sess: &Session, command: &str) -> Result<String> {
+    let mut channel = sess.channel_session()?;
+    channel.exec(command)?;
+
+    let mut stdout = String::new();
+    channel.read_to_string(&mut stdout)?;
+    channel.wait_close()?;
+
+    Ok(stdout.trim().to_string())
+}
+
+/// Parse the `KEY=value` lines of `/etc/os-release` into a JSON object with
+/// lowercase keys.
+fn parse_os_release(content: &str) -> Value {
+    let os: HashMap<String, String> = content
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, val)| {
+            (
+                key.trim().to_lowercase(),
+                val.trim().trim_matches('"').trim_matches('\'').to_string(),
+            )
+        })
+        .collect();
+
+    json!(os)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_os_release_should_work() {
+        let content = r#"
+PRETTY_NAME="Debian GNU/Linux 11 (bullseye)"
+NAME="Debian GNU/Linux"
+VERSION_ID="11"
+ID=debian
+"#;
+
+        assert_eq!(
+            parse_os_release(content),
+            json!({
+              "pretty_name": "Debian GNU/Linux 11 (bullseye)",
+              "name": "Debian GNU/Linux",
+              "version_id": "11",
+              "id": "debian"
+            })
+        );
+    }
+}
@@ -4,4 +4,6 @@ pub mod download;
 pub mod exec;
 pub mod info;
 pub mod module;
+pub mod ping;
+pub mod script;
 pub mod upload;
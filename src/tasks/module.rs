@@ -11,7 +11,7 @@
 //! echo "$data"
 //! ```
 //!
-//! ```no_run 
+//! ```no_run
 //! use serde_json::json;
 //! use tricorder::prelude::*;
 //! use tricorder::tasks::module;
@@ -21,23 +21,23 @@
 //! read -r data
 //! echo echo output: "$data"
 //! "#;
-//! 
+//!
 //! const DATA_FILE: &str = r#"
 //! {
 //!     "data": "data_from_file",
 //!     "overwrittendata":"data from file shoul be overwritten by var modue_mod.sh"
 //! }
 //! "#;
-//! 
+//!
 //! const BINARY_PATH: &str = "/tmp/mod.sh";
 //! const DATA_PATH: &str = "/tmp/data_file.json";
-//! 
+//!
 //! fn main() {
 //!     //write the module file
-//! 
+//!
 //!     std::fs::write(BINARY_PATH, MODULE_FILE).unwrap();
 //!     std::fs::write(DATA_PATH, DATA_FILE).unwrap();
-//! 
+//!
 //!     let inventory = Inventory::new()
 //!         .add_host(
 //!             Host::new(Host::id("localhost").unwrap(), "localhost:22".to_string())
@@ -45,7 +45,7 @@
 //!                 .add_tag(Host::tag("local").unwrap())
 //!                 .set_var("msg".to_string(), json!("hello"))
 //!                 .set_var(
-//!                     // you can define host variables overwriteing the values of the data file 
+//!                     // you can define host variables overwriteing the values of the data file
 //!                     // these variables sould be named "module_<name of the binary>" in This case mod.sh (see BINARY_PATH)
 //!                     "module_mod.sh".to_string(),
 //!                     json!({"overwrittendata":"data_from_var1", "vardata":"data_from_var2"}),
@@ -53,11 +53,11 @@
 //!                 .to_owned(),
 //!         )
 //!         .to_owned();
-//! 
+//!
 //!     let task = module::Task::new(Some(DATA_PATH.to_string()), BINARY_PATH.to_string());
-//! 
+//!
 //!     let result = inventory.hosts.run_task_seq(&task).unwrap();
-//! 
+//!
 //!     println!("{:#?}", result);
 //! }
 //! ```
@@ -68,9 +68,12 @@
 //!     Object {
 //!         "host": String("localhost"),
 //!         "info": Object {
+//!             "changed": Bool(false),
 //!             "exit_code": Number(0),
+//!             "failed": Bool(false),
 //!             "stderr": String(""),
 //!             "stdout": String("echo output: {\"data\":\"data_from_file\",\"overwrittendata\":\"data_from_var1\",\"vardata\":\"data_from_var2\"}\n"),
+//!             "uploaded": Bool(true),
 //!         },
 //!         "success": Bool(true),
 //!     },
@@ -79,38 +82,198 @@
 //!
 //! you can see, that the variable "overwrittendata" gets
 //! overwritten by the host-variable module_mod.sh
+//!
+//! With `Task::set_template()`, the data file is rendered as a template with
+//! the current host as context data before being parsed. String values are
+//! escaped as JSON strings, and the curly braces of JSON objects must be
+//! escaped:
+//!
+//! ```text
+//! \{
+//!   "server_name": "{host.id}.example.com",
+//!   "listen": "{host.address}"
+//! }
+//! ```
+//!
+//! Arguments set with `Task::set_args()` are merged on top of both, so the
+//! precedence is: data file < host variables < arguments. With
+//! `Task::set_verbose()`, the resulting data is included in the result as
+//! `data`.
+//!
+//! # Module output protocol
+//!
+//! If the module writes a valid JSON document on `stdout`, it is embedded in
+//! the result as `result`, and the following well-known keys are lifted into
+//! the result:
+//!
+//! | Key | Description |
+//! | --- | --- |
+//! | `changed` | Boolean, `true` if the module changed something on the host (defaults to `false`) |
+//! | `failed` | Boolean, `true` if the module failed (always `true` for a non-zero exit code) |
+//! | `msg` | Message describing what the module did |
+//! | `diff` | Description of the changes made by the module |
+//!
+//! For example, a module printing `{"changed": true, "msg": "vhost created"}`
+//! results in:
+//!
+//! ```json
+//! {
+//!   "exit_code": 0,
+//!   "stdout": "{\"changed\": true, \"msg\": \"vhost created\"}\n",
+//!   "stderr": "",
+//!   "uploaded": false,
+//!   "changed": true,
+//!   "failed": false,
+//!   "msg": "vhost created",
+//!   "result": {"changed": true, "msg": "vhost created"}
+//! }
+//! ```
+//!
+//! # Remote workspace
+//!
+//! Modules are uploaded to the `modules/` directory of the remote workspace,
+//! `~/.local/tricorder` by default. It can be changed with
+//! `Task::set_workspace()`, for example for hosts with a `noexec` home. A
+//! leading `~` or `~user` is expanded on the remote host.
+//!
+//! With `Task::set_cleanup()`, the module is removed from the remote host
+//! after its execution. Otherwise, the modules unused for some time can be
+//! removed with a `GcTask`, which reports the modules it actually removed
+//! (none with `GcTask::set_dry_run()`), and sets `failed` if some stale
+//! modules could not be removed:
+//!
+//! ```no_run
+//! use tricorder::prelude::*;
+//! use tricorder::tasks::module;
+//!
+//! let inventory = Inventory::new()
+//!   .add_host(Host::new(Host::id("localhost").unwrap(), "localhost:22".to_string()))
+//!   .to_owned();
+//!
+//! // remove modules unused for 30 days
+//! let task = module::GcTask::new(30);
+//! let result = inventory.hosts.run_task_seq(&task).unwrap();
+//! ```
+//!
+//! Which results in:
+//!
+//! ```json
+//! [
+//!   {
+//!     "host": "localhost",
+//!     "success": true,
+//!     "info": {
+//!       "modules": ["mod.sh", "nginx_vhost"],
+//!       "stale": ["mod.sh"],
+//!       "pruned": ["mod.sh"]
+//!     }
+//!   }
+//! ]
+//! ```
+//!
+//! The module is only uploaded if the remote copy does not have the same
+//! SHA-256 digest (checked with `sha256sum`, `shasum -a 256` or `openssl dgst`
+//! on the remote host, whichever is available) as the local one, which is
+//! reported by the `uploaded` key. If none of those tools is available, the
+//! task fails: use `Task::set_force_upload()` to always upload the module.
 
 use crate::prelude::*;
 
 use serde_json::{json, Value};
-use ssh2::Channel;
+use ssh2::Session;
+use tinytemplate::{format_unescaped, TinyTemplate};
 
-use std::fs::{self, File};
+use std::fs;
 use std::io::prelude::*;
 use std::path::Path;
+use std::sync::OnceLock;
+
+const DEFAULT_WORKSPACE: &str = "~/.local/tricorder";
 
 /// Describe an `module` task
 pub struct Task {
     data_path: Option<String>,
+    /// If true, `data_path` is treated as a template
+    is_template: bool,
     module_path: String,
     module_name: String,
+    /// If true, upload the module even if an identical copy is already present
+    /// on the remote host
+    force_upload: bool,
+    /// SHA-256 digest of the module, computed once for all hosts
+    module_hash: OnceLock<String>,
+    /// Data merged on top of the data file and the host variables
+    args: Option<Value>,
+    /// If true, the data sent to the module is included in the result
+    verbose: bool,
+    /// Remote workspace directory, modules are uploaded to its `modules/`
+    /// subdirectory
+    workspace: Option<String>,
+    /// If true, the module is removed from the remote host after its execution
+    cleanup: bool,
 }
 
 impl Task {
     /// Create a new `module` task
-
     pub fn new(data_path: Option<String>, module_path: String) -> Self {
-        let module_name = module_path.split("/").last().unwrap().to_owned();
+        let module_name = module_path.rsplit('/').next().unwrap().to_owned();
         Self {
             data_path,
+            is_template: false,
             module_path,
             module_name,
+            force_upload: false,
+            module_hash: OnceLock::new(),
+            args: None,
+            verbose: false,
+            workspace: None,
+            cleanup: false,
         }
     }
+
+    /// Treat the data file as a template with the current host as context
+    /// data, rendered before being parsed as JSON
+    pub fn set_template(&mut self, is_template: bool) -> &mut Self {
+        self.is_template = is_template;
+        self
+    }
+
+    /// Set the arguments merged on top of the data file and the host variables
+    pub fn set_args(&mut self, args: Value) -> &mut Self {
+        self.args = Some(args);
+        self
+    }
+
+    /// Include the data sent to the module in the result
+    pub fn set_verbose(&mut self, verbose: bool) -> &mut Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Override the remote workspace directory (defaults to `~/.local/tricorder`)
+    pub fn set_workspace(&mut self, workspace: String) -> &mut Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Remove the module from the remote host after its execution
+    pub fn set_cleanup(&mut self, cleanup: bool) -> &mut Self {
+        self.cleanup = cleanup;
+        self
+    }
+
+    /// Always upload the module, even if an identical copy is already present
+    /// on the remote host
+    pub fn set_force_upload(&mut self, force_upload: bool) -> &mut Self {
+        self.force_upload = force_upload;
+        self
+    }
 }
 
 impl GenericTask<Value> for Task {
     fn prepare(&self, host: Host) -> Result<Value> {
+        self.module_hash()?;
+
         let hostvars = host.vars.clone();
 
         let default = json!({});
@@ -119,29 +282,56 @@ impl GenericTask<Value> for Task {
             .get(&format!("module_{}", self.module_name))
             .unwrap_or(&default);
 
-        if let Some(datapath) = self.data_path.clone() {
-            let mut data: Value = fs::read_to_string(datapath)?.parse()?;
+        let mut data = if let Some(datapath) = self.data_path.clone() {
+            let mut content = fs::read_to_string(datapath)?;
+
+            if self.is_template {
+                content = render_data(&content, &host)?;
+            }
+
+            let mut data: Value = content.parse()?;
 
             merge(&mut data, host_var_data);
-            Ok(data)
+            data
         } else {
-            Ok(host_var_data.clone())
+            host_var_data.clone()
+        };
+
+        if let Some(args) = &self.args {
+            merge(&mut data, args);
         }
+
+        Ok(data)
     }
 
     fn apply(&self, host: Host, data: Value) -> TaskResult {
         let sess = host.get_session()?;
 
-        let mut channel = sess.channel_session()?;
-        channel.exec("echo $HOME")?;
+        let modules_dir = get_modules_dir(&sess, self.workspace.as_deref())?;
+        let remote_path = format!("{}/{}", modules_dir, self.module_name);
+        let quoted_path = shell_words::quote(&remote_path);
 
-        let mut home_path = String::new();
-        channel.read_to_string(&mut home_path)?;
-        channel.wait_close()?;
+        let uploaded = if self.force_upload || !self.is_module_uploaded(&sess, &remote_path)? {
+            self.upload_module(&sess, &modules_dir, &remote_path)?;
+            true
+        } else {
+            // refresh the modification time, so that the module is not
+            // considered stale by the `GcTask`
+            run_command(&sess, &format!("touch {}", quoted_path))?;
+            false
+        };
+
+        let echoed_data = if self.verbose {
+            Some(data.clone())
+        } else {
+            None
+        };
 
-        self.upload_module(&host, home_path.trim())?;
+        let mut channel = sess.channel_session()?;
+        channel.exec(&quoted_path)?;
 
-        let mut channel = self.execute_module(&host, data)?;
+        channel.write_all(serde_json::to_string(&data)?.as_bytes())?;
+        channel.send_eof()?;
 
         let mut stdout = String::new();
         channel.read_to_string(&mut stdout)?;
@@ -152,47 +342,69 @@ impl GenericTask<Value> for Task {
 
         let exit_code = channel.exit_status()?;
 
-        Ok(json!({
-          "exit_code": exit_code,
-          "stdout": stdout,
-          "stderr": stderr,
-        }))
+        if self.cleanup {
+            run_command(&sess, &cleanup_command(&remote_path))?;
+        }
+
+        let mut output = module_output(exit_code, stdout, stderr);
+        output["uploaded"] = json!(uploaded);
+
+        if let Some(data) = echoed_data {
+            output["data"] = data;
+        }
+
+        Ok(output)
     }
 }
+
 impl Task {
-    fn execute_module(&self, host: &Host, data: Value) -> Result<Channel> {
-        let sess = host.get_session()?;
-        let mut channel = sess.channel_session()?;
-        channel.exec(&format!("~/.local/tricorder/modules/{}", self.module_name))?;
+    fn module_hash(&self) -> Result<&str> {
+        if let Some(hash) = self.module_hash.get() {
+            return Ok(hash);
+        }
 
-        channel.write_all(serde_json::to_string(&data)?.as_bytes())?;
-        channel.send_eof()?;
-        Ok(channel)
+        let module_binary = fs::read(&self.module_path)?;
+        Ok(self.module_hash.get_or_init(|| sha256_hex(&module_binary)))
     }
 
-    fn upload_module(&self, host: &Host, home_path: &str) -> Result<()> {
-        let sess = host.get_session()?;
+    /// Check if the module on the remote host has the same content hash as
+    /// the local one
+    fn is_module_uploaded(&self, sess: &Session, remote_path: &str) -> Result<bool> {
+        let (exit_code, output) = run_command(sess, &remote_hash_command(remote_path))?;
 
-        // create folder
-        let mut channel = sess.channel_session()?;
-        channel.exec("mkdir -p ~/.local/tricorder/modules")?;
+        if exit_code == MISSING_HASH_TOOL_EXIT_CODE {
+            return Err(Box::new(Error::CommandExecutionFailed(String::from(
+                "No SHA-256 tool (sha256sum, shasum or openssl) found on the host to compare the module, use force_upload to upload it anyway",
+            ))));
+        }
 
-        let mut module_binary_file = File::open(format!("{}", self.module_path))?;
+        let remote_hash = output.split_whitespace().next().unwrap_or("");
+        Ok(remote_hash == self.module_hash()?)
+    }
 
-        let mut module_binary: Vec<u8> = vec![];
-        module_binary_file.read_to_end(&mut module_binary)?;
+    fn upload_module(&self, sess: &Session, modules_dir: &str, remote_path: &str) -> Result<()> {
+        let (exit_code, _) = run_command(
+            sess,
+            &format!("mkdir -p {}", shell_words::quote(modules_dir)),
+        )?;
+
+        if exit_code != 0 {
+            return Err(Box::new(Error::CommandExecutionFailed(format!(
+                "Failed to create module directory {}: exit code {}",
+                modules_dir, exit_code
+            ))));
+        }
+
+        let module_binary = fs::read(&self.module_path)?;
 
         let mut remote_file = sess.scp_send(
-            Path::new(&format!(
-                "{}/.local/tricorder/modules/{}",
-                home_path, self.module_name
-            )),
+            Path::new(remote_path),
             0o700,
             module_binary.len() as u64,
             None,
         )?;
 
-        remote_file.write(&module_binary)?;
+        remote_file.write_all(&module_binary)?;
         // Close the channel and wait for the whole content to be transferred
         remote_file.send_eof()?;
         remote_file.wait_eof()?;
@@ -203,6 +415,286 @@ impl Task {
     }
 }
 
+/// Describe a task listing the modules uploaded on a remote host, and removing
+/// the stale ones: modules which were neither uploaded nor executed for a
+/// given number of days.
+pub struct GcTask {
+    /// Remote workspace directory
+    workspace: Option<String>,
+    /// Number of days after which an unused module is stale
+    max_age_days: u64,
+    /// If true, stale modules are listed but not removed
+    dry_run: bool,
+}
+
+impl GcTask {
+    /// Create a new task removing modules unused for `max_age_days` days
+    pub fn new(max_age_days: u64) -> Self {
+        Self {
+            workspace: None,
+            max_age_days,
+            dry_run: false,
+        }
+    }
+
+    /// Override the remote workspace directory (defaults to `~/.local/tricorder`)
+    pub fn set_workspace(&mut self, workspace: String) -> &mut Self {
+        self.workspace = Some(workspace);
+        self
+    }
+
+    /// Only list the stale modules, without removing them
+    pub fn set_dry_run(&mut self, dry_run: bool) -> &mut Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+impl GenericTask<()> for GcTask {
+    fn prepare(&self, _host: Host) -> Result<()> {
+        Ok(())
+    }
+
+    fn apply(&self, host: Host, _data: ()) -> TaskResult {
+        let sess = host.get_session()?;
+
+        let modules_dir = get_modules_dir(&sess, self.workspace.as_deref())?;
+        let (_, modules) = run_command(&sess, &modules_command(&modules_dir))?;
+        let (_, stale) = run_command(
+            &sess,
+            &stale_modules_command(&modules_dir, self.max_age_days),
+        )?;
+
+        let pruned = if self.dry_run {
+            None
+        } else if stale.trim().is_empty() {
+            Some(String::new())
+        } else {
+            let paths: Vec<&str> = stale.lines().collect();
+            let (_, pruned) = run_command(&sess, &prune_command(&paths))?;
+            Some(pruned)
+        };
+
+        Ok(gc_output(&modules, &stale, pruned.as_deref()))
+    }
+}
+
+/// Shell command listing the modules of the modules directory
+fn modules_command(modules_dir: &str) -> String {
+    format!(
+        "find {} -maxdepth 1 -type f 2>/dev/null",
+        shell_words::quote(modules_dir)
+    )
+}
+
+/// Shell command listing the modules unused for `max_age_days` days
+fn stale_modules_command(modules_dir: &str, max_age_days: u64) -> String {
+    format!(
+        "find {} -maxdepth 1 -type f -mmin +{} 2>/dev/null",
+        shell_words::quote(modules_dir),
+        max_age_days * 24 * 60
+    )
+}
+
+/// Render a data file template, with the values escaped as JSON strings
+fn render_data(content: &str, host: &Host) -> Result<String> {
+    let mut tt = TinyTemplate::new();
+    tt.set_default_formatter(&format_json_string);
+    tt.add_template("data", content)?;
+
+    let ctx = json!({ "host": host });
+    Ok(tt.render("data", &ctx)?)
+}
+
+/// Format strings as the content of a JSON string, so that quotes and
+/// backslashes in host variables do not break the rendered document
+fn format_json_string(value: &Value, output: &mut String) -> tinytemplate::error::Result<()> {
+    match value {
+        Value::String(s) => {
+            let quoted = Value::String(s.clone()).to_string();
+            output.push_str(&quoted[1..quoted.len() - 1]);
+            Ok(())
+        }
+        _ => format_unescaped(value, output),
+    }
+}
+
+/// Shell command removing the modules, and printing the ones which were
+/// actually removed
+fn prune_command(paths: &[&str]) -> String {
+    format!(
+        "for path in {}; do rm -- \"$path\" 2>/dev/null && echo \"$path\"; done; true",
+        shell_words::join(paths)
+    )
+}
+
+/// Shell command removing a module after its execution
+fn cleanup_command(remote_path: &str) -> String {
+    format!("rm -f -- {}", shell_words::quote(remote_path))
+}
+
+/// Build the result of a `GcTask` from the output of the `find` and `rm`
+/// commands (`None` for a dry run).
+fn gc_output(modules: &str, stale: &str, pruned: Option<&str>) -> Value {
+    let stale = module_names(stale);
+    let dry_run = pruned.is_none();
+    let pruned = module_names(pruned.unwrap_or(""));
+    let failed = !dry_run && pruned.len() < stale.len();
+
+    let mut output = json!({
+      "modules": module_names(modules),
+      "stale": stale,
+      "pruned": pruned,
+    });
+
+    if failed {
+        output["failed"] = json!(true);
+    }
+
+    output
+}
+
+/// Get the absolute path to the modules directory in the remote workspace
+fn get_modules_dir(sess: &Session, workspace: Option<&str>) -> Result<String> {
+    let workspace = workspace.unwrap_or(DEFAULT_WORKSPACE);
+
+    let home_path = match home_prefix(workspace)? {
+        Some(prefix) => {
+            // `~` and `~user` are expanded by the remote shell
+            let (_, home_path) = run_command(sess, &format!("echo {}", prefix))?;
+            let home_path = home_path.trim();
+
+            if home_path.is_empty() || home_path.starts_with('~') {
+                return Err(Box::new(Error::CommandExecutionFailed(format!(
+                    "Failed to expand '{}' in workspace {}",
+                    prefix, workspace
+                ))));
+            }
+
+            home_path.to_string()
+        }
+        None => String::new(),
+    };
+
+    Ok(modules_dir(workspace, &home_path))
+}
+
+/// The `~` or `~user` prefix of a home-relative workspace, `None` if the
+/// workspace is not home-relative
+fn home_prefix(workspace: &str) -> Result<Option<&str>> {
+    if !workspace.starts_with('~') {
+        return Ok(None);
+    }
+
+    let prefix = workspace.split('/').next().unwrap();
+    let user = &prefix[1..];
+
+    // the prefix is expanded by the remote shell, so it must not contain
+    // anything but a user name
+    if !user
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        return Err(Box::new(Error::InvalidArgument(format!(
+            "Invalid user name '{}' in workspace {}",
+            user, workspace
+        ))));
+    }
+
+    Ok(Some(prefix))
+}
+
+/// Path to the modules directory of a workspace, its `~` or `~user` prefix
+/// being replaced by `home_path`
+fn modules_dir(workspace: &str, home_path: &str) -> String {
+    let workspace = match workspace.strip_prefix('~') {
+        Some(rest) => {
+            let rest = rest.find('/').map_or("", |pos| &rest[pos..]);
+            format!("{}{}", home_path, rest)
+        }
+        None => workspace.to_string(),
+    };
+
+    format!("{}/modules", workspace.trim_end_matches('/'))
+}
+
+/// Exit code of `remote_hash_command()` if no SHA-256 tool is available
+const MISSING_HASH_TOOL_EXIT_CODE: i32 = 127;
+
+/// Shell command printing the SHA-256 digest of a remote file, in the format
+/// of `sha256sum`, with the first tool available on the host.
+fn remote_hash_command(remote_path: &str) -> String {
+    let path = shell_words::quote(remote_path);
+
+    format!(
+        "if command -v sha256sum >/dev/null 2>&1; then sha256sum {path} 2>/dev/null; \
+         elif command -v shasum >/dev/null 2>&1; then shasum -a 256 {path} 2>/dev/null; \
+         elif command -v openssl >/dev/null 2>&1; then openssl dgst -sha256 -r {path} 2>/dev/null; \
+         else exit {code}; fi",
+        path = path,
+        code = MISSING_HASH_TOOL_EXIT_CODE
+    )
+}
+
+/// Execute a command on the remote host, and return its exit code and output
+fn run_command(sess: &Session, command: &str) -> Result<(i32, String)> {
+    let mut channel = sess.channel_session()?;
+    channel.exec(command)?;
+
+    let mut stdout = String::new();
+    channel.read_to_string(&mut stdout)?;
+    channel.wait_close()?;
+
+    Ok((channel.exit_status()?, stdout))
+}
+
+fn module_names(find_output: &str) -> Vec<String> {
+    let mut names: Vec<String> = find_output
+        .lines()
+        .filter_map(|path| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .map(String::from)
+        .collect();
+    names.sort();
+    names
+}
+
+/// Build the result of a module execution according to the module output
+/// protocol: if `stdout` is a valid JSON document, it is embedded as `result`,
+/// and the well-known keys `changed`, `failed`, `msg` and `diff` are lifted
+/// into the task's result.
+fn module_output(exit_code: i32, stdout: String, stderr: String) -> Value {
+    let result: Option<Value> = serde_json::from_str(&stdout).ok();
+
+    let mut output = json!({
+      "exit_code": exit_code,
+      "stdout": stdout,
+      "stderr": stderr,
+      "changed": false,
+      "failed": exit_code != 0,
+    });
+
+    if let Some(result) = result {
+        if let Some(changed) = result.get("changed").and_then(Value::as_bool) {
+            output["changed"] = json!(changed);
+        }
+
+        if let Some(failed) = result.get("failed").and_then(Value::as_bool) {
+            output["failed"] = json!(failed || exit_code != 0);
+        }
+
+        for key in ["msg", "diff"] {
+            if let Some(val) = result.get(key) {
+                output[key] = val.clone();
+            }
+        }
+
+        output["result"] = result;
+    }
+
+    output
+}
+
 fn merge(a: &mut Value, b: &Value) {
     match (a, b) {
         (&mut Value::Object(ref mut a), &Value::Object(ref b)) => {
@@ -215,3 +707,183 @@ fn merge(a: &mut Value, b: &Value) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestDir;
+
+    use std::process::Command;
+
+    fn run_shell(command: &str) -> String {
+        let output = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(command)
+            .output()
+            .unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    #[test]
+    fn render_data_should_escape_strings_as_json() {
+        let mut host = Host::new(Host::id("web-1").unwrap(), "10.0.0.1:22".to_string());
+        host.set_var("motd".to_string(), json!("say \"hi\" from C:\\"));
+        host.set_var("port".to_string(), json!(8080));
+
+        let content = r#"\{ "server_name": "{host.id}", "motd": "{host.vars.motd}", "port": {host.vars.port} }"#;
+        let data: Value = render_data(content, &host).unwrap().parse().unwrap();
+
+        assert_eq!(
+            data,
+            json!({"server_name": "web-1", "motd": "say \"hi\" from C:\\", "port": 8080})
+        );
+    }
+
+    #[test]
+    fn modules_dir_should_expand_the_workspace() {
+        assert_eq!(
+            modules_dir(DEFAULT_WORKSPACE, "/home/user"),
+            "/home/user/.local/tricorder/modules"
+        );
+        assert_eq!(modules_dir("~", "/root"), "/root/modules");
+        assert_eq!(
+            modules_dir("/opt/tricorder/", "/root"),
+            "/opt/tricorder/modules"
+        );
+        assert_eq!(
+            modules_dir("~user/ws", "/home/user"),
+            "/home/user/ws/modules"
+        );
+        assert_eq!(modules_dir("~user", "/home/user"), "/home/user/modules");
+    }
+
+    #[test]
+    fn home_prefix_should_only_accept_user_names() {
+        assert_eq!(home_prefix("/opt/tricorder").unwrap(), None);
+        assert_eq!(home_prefix(DEFAULT_WORKSPACE).unwrap(), Some("~"));
+        assert_eq!(home_prefix("~").unwrap(), Some("~"));
+        assert_eq!(home_prefix("~deploy-1/ws").unwrap(), Some("~deploy-1"));
+        assert!(home_prefix("~$(reboot)/ws").is_err());
+        assert!(home_prefix("~user name/ws").is_err());
+    }
+
+    #[test]
+    fn gc_commands_should_quote_paths() {
+        assert_eq!(
+            stale_modules_command("/opt/my ws/modules", 2),
+            "find '/opt/my ws/modules' -maxdepth 1 -type f -mmin +2880 2>/dev/null"
+        );
+        assert_eq!(
+            modules_command("/opt/ws/modules"),
+            "find /opt/ws/modules -maxdepth 1 -type f 2>/dev/null"
+        );
+        assert_eq!(
+            cleanup_command("/opt/my ws/modules/mod.sh"),
+            "rm -f -- '/opt/my ws/modules/mod.sh'"
+        );
+    }
+
+    #[test]
+    fn prune_command_should_print_the_removed_modules() {
+        let dir = TestDir::new("prune");
+        let stale = dir.join("stale mod.sh");
+        fs::write(&stale, "").unwrap();
+        let missing = dir.join("missing.sh");
+
+        let stale_path = stale.display().to_string();
+        let missing_path = missing.display().to_string();
+        let pruned = run_shell(&prune_command(&[&stale_path, &missing_path]));
+
+        assert!(!stale.exists());
+        assert_eq!(pruned, format!("{}\n", stale_path));
+    }
+
+    #[test]
+    fn gc_output_should_report_the_pruned_modules() {
+        let modules = "/ws/modules/a.sh\n/ws/modules/b.sh\n";
+        let stale = "/ws/modules/a.sh\n/ws/modules/b.sh\n";
+
+        assert_eq!(
+            gc_output(modules, stale, None),
+            json!({"modules": ["a.sh", "b.sh"], "stale": ["a.sh", "b.sh"], "pruned": []})
+        );
+        assert_eq!(
+            gc_output(modules, stale, Some(stale)),
+            json!({"modules": ["a.sh", "b.sh"], "stale": ["a.sh", "b.sh"], "pruned": ["a.sh", "b.sh"]})
+        );
+        assert_eq!(
+            gc_output(modules, stale, Some("/ws/modules/b.sh\n")),
+            json!({
+              "modules": ["a.sh", "b.sh"],
+              "stale": ["a.sh", "b.sh"],
+              "pruned": ["b.sh"],
+              "failed": true,
+            })
+        );
+        assert_eq!(
+            gc_output(modules, "", Some("")),
+            json!({"modules": ["a.sh", "b.sh"], "stale": [], "pruned": []})
+        );
+    }
+
+    #[test]
+    fn remote_hash_command_should_print_the_digest() {
+        let dir = TestDir::new("remote-hash");
+        let path = dir.join("module");
+        fs::write(&path, "abc").unwrap();
+
+        let output = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(remote_hash_command(&path.display().to_string()))
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(
+            stdout.split_whitespace().next(),
+            Some(sha256_hex(b"abc").as_str())
+        );
+    }
+
+    #[test]
+    fn remote_hash_command_should_fail_without_hash_tool() {
+        let output = Command::new("/bin/sh")
+            .arg("-c")
+            .arg(remote_hash_command("/nonexistent"))
+            .env("PATH", "/nonexistent")
+            .output()
+            .unwrap();
+
+        assert_eq!(output.status.code(), Some(MISSING_HASH_TOOL_EXIT_CODE));
+    }
+
+    #[test]
+    fn module_output_should_lift_well_known_keys() {
+        let stdout = r#"{"changed": true, "msg": "done", "diff": "+foo", "extra": 1}"#;
+
+        assert_eq!(
+            module_output(0, stdout.to_string(), "".to_string()),
+            json!({
+              "exit_code": 0,
+              "stdout": stdout,
+              "stderr": "",
+              "changed": true,
+              "failed": false,
+              "msg": "done",
+              "diff": "+foo",
+              "result": {"changed": true, "msg": "done", "diff": "+foo", "extra": 1}
+            })
+        );
+    }
+
+    #[test]
+    fn module_output_should_fail_on_non_zero_exit_code() {
+        let output = module_output(1, r#"{"failed": false}"#.to_string(), "".to_string());
+        assert_eq!(output["failed"], json!(true));
+        assert_eq!(output["changed"], json!(false));
+
+        let output = module_output(0, "not json".to_string(), "".to_string());
+        assert_eq!(output["failed"], json!(false));
+        assert_eq!(output.get("result"), None);
+    }
+}
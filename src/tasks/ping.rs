@@ -0,0 +1,251 @@
+//! Check connectivity and authentication to hosts
+//!
+//! Example usage:
+//!
+//! ```no_run
+//! use tricorder::prelude::*;
+//! use tricorder::tasks::ping;
+//!
+//! let inventory = Inventory::new()
+//!   .add_host(
+//!     Host::new(Host::id("localhost").unwrap(), "localhost:22".to_string())
+//!       .set_user("root".to_string())
+//!       .to_owned()
+//!   )
+//!   .to_owned();
+//!
+//! let task = ping::Task::new()
+//!   .set_connect_timeout(5)
+//!   .to_owned();
+//! let result = inventory.hosts.run_task_parallel(&task).unwrap();
+//! ```
+//!
+//! The TCP connection, and each step of the SSH session, times out after the
+//! host's `connect_timeout`, or after the task's one (10 seconds by default)
+//! if the host has none.
+//!
+//! The result is a JSON document with the following structure (latencies are
+//! in milliseconds):
+//!
+//! ```json
+//! [
+//!   {
+//!     "host": "example-0",
+//!     "success": true,
+//!     "info": {
+//!       "connect_ms": 0.52,
+//!       "handshake_ms": 12.3,
+//!       "auth_ms": 4.1,
+//!       "exec_ms": 8.7
+//!     }
+//!   },
+//!   {
+//!     "host": "example-1",
+//!     "success": false,
+//!     "category": "connection_refused",
+//!     "error": "HostUnreachable { category: \"connection_refused\", reason: \"Connection refused (os error 111)\" }"
+//!   }
+//! ]
+//! ```
+//!
+//! The `category` of unreachable hosts is one of:
+//!
+//! | Category | Description |
+//! | --- | --- |
+//! | `dns` | The host's address could not be resolved |
+//! | `connection_refused` | Nothing is listening on the host's SSH port |
+//! | `timeout` | The TCP connection timed out |
+//! | `network_unreachable` | No route to the host |
+//! | `connect` | Any other TCP connection failure |
+//! | `handshake` | The SSH handshake failed |
+//! | `authentication` | The `ssh-agent` authentication failed |
+//! | `exec` | The no-op command could not be executed |
+
+use crate::prelude::*;
+
+use serde_json::json;
+
+use std::{
+    error::Error as BaseError,
+    io::{self, prelude::*},
+    time::Instant,
+};
+
+/// Default timeout of the TCP connection, in seconds
+const DEFAULT_CONNECT_TIMEOUT: u64 = 10;
+
+/// Describe a `ping` task
+#[derive(Clone)]
+pub struct Task {
+    /// Timeout of the TCP connection to the hosts without their own, in
+    /// seconds
+    connect_timeout: u64,
+}
+
+impl Task {
+    /// Create a new `ping` task
+    pub fn new() -> Self {
+        Self {
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+        }
+    }
+
+    /// Set the timeout of the TCP connection to the hosts without their own,
+    /// in seconds
+    pub fn set_connect_timeout(&mut self, connect_timeout: u64) -> &mut Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+}
+
+impl Default for Task {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GenericTask<()> for Task {
+    fn prepare(&self, _host: Host) -> Result<()> {
+        Ok(())
+    }
+
+    fn apply(&self, host: Host, _data: ()) -> TaskResult {
+        let mut host = host;
+
+        if host.connect_timeout.is_none() {
+            host.set_connect_timeout(self.connect_timeout);
+        }
+
+        let timer = Instant::now();
+        let sock = host
+            .connect()
+            .map_err(|err| unreachable(connect_error_category(err.as_ref()), err))?;
+        let connect_ms = elapsed_ms(timer);
+
+        let timer = Instant::now();
+        let sess = host
+            .handshake(sock)
+            .map_err(|err| unreachable("handshake", err))?;
+        let handshake_ms = elapsed_ms(timer);
+
+        let timer = Instant::now();
+        host.authenticate(&sess)
+            .map_err(|err| unreachable("authentication", err))?;
+        let auth_ms = elapsed_ms(timer);
+
+        let timer = Instant::now();
+        noop(&sess).map_err(|err| unreachable("exec", err))?;
+        let exec_ms = elapsed_ms(timer);
+
+        Ok(json!({
+          "connect_ms": connect_ms,
+          "handshake_ms": handshake_ms,
+          "auth_ms": auth_ms,
+          "exec_ms": exec_ms,
+        }))
+    }
+}
+
+fn noop(sess: &ssh2::Session) -> Result<()> {
+    let mut channel = sess.channel_session()?;
+    channel.exec("true")?;
+
+    let mut stdout = String::new();
+    channel.read_to_string(&mut stdout)?;
+    channel.wait_close()?;
+
+    Ok(())
+}
+
+fn elapsed_ms(timer: Instant) -> f64 {
+    timer.elapsed().as_secs_f64() * 1000.0
+}
+
+fn unreachable(category: &str, err: Box<dyn BaseError + Send + Sync>) -> Error {
+    Error::HostUnreachable {
+        category: category.to_string(),
+        reason: err.to_string(),
+    }
+}
+
+fn connect_error_category(err: &(dyn BaseError + Send + Sync + 'static)) -> &'static str {
+    match err.downcast_ref::<io::Error>() {
+        Some(err) => match err.kind() {
+            io::ErrorKind::ConnectionRefused => "connection_refused",
+            io::ErrorKind::TimedOut => "timeout",
+            io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable => {
+                "network_unreachable"
+            }
+            _ if err.to_string().contains("lookup address") => "dns",
+            _ => "connect",
+        },
+        None => "connect",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connect_error_category_should_map_io_errors() {
+        let category = |kind: io::ErrorKind, msg: &str| {
+            let err: Box<dyn BaseError + Send + Sync> = Box::new(io::Error::new(kind, msg));
+            connect_error_category(err.as_ref())
+        };
+
+        assert_eq!(
+            category(io::ErrorKind::ConnectionRefused, "refused"),
+            "connection_refused"
+        );
+        assert_eq!(category(io::ErrorKind::TimedOut, "timed out"), "timeout");
+        assert_eq!(
+            category(io::ErrorKind::HostUnreachable, "no route"),
+            "network_unreachable"
+        );
+        assert_eq!(
+            category(io::ErrorKind::NetworkUnreachable, "no route"),
+            "network_unreachable"
+        );
+        assert_eq!(
+            category(io::ErrorKind::Other, "failed to lookup address information"),
+            "dns"
+        );
+        assert_eq!(category(io::ErrorKind::Other, "other"), "connect");
+
+        let err: Box<dyn BaseError + Send + Sync> = Box::new(Error::Other(String::from("other")));
+        assert_eq!(connect_error_category(err.as_ref()), "connect");
+    }
+
+    #[test]
+    fn ping_should_report_the_category_of_unreachable_hosts() {
+        let hosts = vec![Host::new(
+            Host::id("closed").unwrap(),
+            String::from("127.0.0.1:1"),
+        )];
+
+        let res = hosts.run_task_seq(&Task::new()).unwrap();
+
+        assert_eq!(res[0]["success"], json!(false));
+        assert_eq!(res[0]["category"], json!("connection_refused"));
+    }
+
+    #[test]
+    fn ping_should_time_out_on_silent_hosts() {
+        // accepts the connection, but never sends the SSH banner
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let hosts = vec![Host::new(
+            Host::id("silent").unwrap(),
+            listener.local_addr().unwrap().to_string(),
+        )];
+
+        let timer = Instant::now();
+        let res = hosts
+            .run_task_seq(Task::new().set_connect_timeout(1))
+            .unwrap();
+
+        assert!(timer.elapsed().as_secs() < 5);
+        assert_eq!(res[0]["success"], json!(false));
+        assert_eq!(res[0]["category"], json!("handshake"));
+    }
+}
@@ -0,0 +1,226 @@
+//! Run a local script on a remote host, without leaving files behind
+//!
+//! The script is streamed to the standard input of its interpreter on the
+//! remote host. The interpreter is detected from the script's shebang (`sh` is
+//! used if there is none), and is invoked so that it reads the script from its
+//! standard input:
+//!
+//! | Shebang | Remote command |
+//! | --- | --- |
+//! | `#!/bin/bash` | `/bin/bash -s -- ARGS...` |
+//! | `#!/usr/bin/env python3` | `python3 - ARGS...` |
+//! | *none* | `sh -s -- ARGS...` |
+//!
+//! Shells (`sh`, `bash`, `dash`, `ksh`, `zsh`) are invoked with `-s --`, any
+//! other interpreter with `-`. The command can be overridden with
+//! `Task::set_interpreter()`.
+//!
+//! > **NB:** Since the script is read from the standard input, it cannot
+//! > read data from it.
+//!
+//! Example usage:
+//!
+//! ```no_run
+//! use tricorder::prelude::*;
+//! use tricorder::tasks::script;
+//! use serde_json::json;
+//!
+//! let inventory = Inventory::new()
+//!   .add_host(
+//!     Host::new(Host::id("localhost").unwrap(), "localhost:22".to_string())
+//!       .set_user("root".to_string())
+//!       .add_tag(Host::tag("local").unwrap())
+//!       .set_var("msg".to_string(), json!("hello"))
+//!       .to_owned()
+//!   )
+//!   .to_owned();
+//!
+//! let task = script::Task::new_template(
+//!   "/path/to/local/script.sh".to_string(),
+//!   vec!["--verbose".to_string()],
+//! );
+//! let result = inventory.hosts.run_task_seq(&task).unwrap();
+//! ```
+//!
+//! The result is a JSON document with the following structure:
+//!
+//! ```json
+//! [
+//!   {
+//!     "host": "example-0",
+//!     "success": true,
+//!     "info": {
+//!       "exit_code": 0,
+//!       "stdout": "...",
+//!       "stderr": "..."
+//!     }
+//!   },
+//!   {
+//!     "host": "example-1",
+//!     "success": false,
+//!     "error": "..."
+//!   }
+//! ]
+//! ```
+
+use crate::prelude::*;
+
+use serde_json::json;
+use tinytemplate::{format_unescaped, TinyTemplate};
+
+use std::{fs, io::prelude::*, path::Path};
+
+const SHELLS: [&str; 5] = ["sh", "bash", "dash", "ksh", "zsh"];
+
+/// Describe a `script` task
+pub struct Task {
+    /// If true, `script_path` is treated as a template
+    is_template: bool,
+    /// Path to local script to run
+    script_path: String,
+    /// Arguments given to the script
+    args: Vec<String>,
+    /// Command reading the script from its standard input, detected from the
+    /// script's shebang if not set
+    interpreter: Option<String>,
+}
+
+impl Task {
+    /// Create a new `script` task where `script_path` is a template
+    pub fn new_template(script_path: String, args: Vec<String>) -> Self {
+        Self {
+            is_template: true,
+            script_path,
+            args,
+            interpreter: None,
+        }
+    }
+
+    /// Create a new `script` task where `script_path` is a static file
+    pub fn new_file(script_path: String, args: Vec<String>) -> Self {
+        Self {
+            is_template: false,
+            script_path,
+            args,
+            interpreter: None,
+        }
+    }
+
+    /// Override the command reading the script from its standard input.
+    ///
+    /// Example: `"python3 -"`
+    pub fn set_interpreter(&mut self, interpreter: String) -> &mut Self {
+        self.interpreter = Some(interpreter);
+        self
+    }
+}
+
+impl GenericTask<String> for Task {
+    fn prepare(&self, host: Host) -> Result<String> {
+        let script_path = Path::new(self.script_path.as_str());
+
+        if !script_path.exists() {
+            return Err(Box::new(Error::FileNotFound(format!(
+                "No such file: {}",
+                self.script_path
+            ))));
+        } else if script_path.is_dir() {
+            return Err(Box::new(Error::IsADirectory(format!(
+                "Path is a directory, not a file: {}",
+                self.script_path
+            ))));
+        }
+
+        let script = fs::read_to_string(script_path)?;
+
+        if self.is_template {
+            let mut tt = TinyTemplate::new();
+            tt.set_default_formatter(&format_unescaped);
+            tt.add_template("script", script.as_str())?;
+
+            let ctx = json!({ "host": host });
+            let content = tt.render("script", &ctx)?;
+            Ok(content)
+        } else {
+            Ok(script)
+        }
+    }
+
+    fn apply(&self, host: Host, script: String) -> TaskResult {
+        let interpreter = match &self.interpreter {
+            Some(interpreter) => interpreter.clone(),
+            None => detect_interpreter(&script),
+        };
+        let command = format!("{} {}", interpreter, shell_words::join(&self.args));
+
+        let sess = host.get_session()?;
+        let mut channel = sess.channel_session()?;
+        channel.exec(command.trim_end())?;
+
+        channel.write_all(script.as_bytes())?;
+        channel.send_eof()?;
+
+        let mut stdout = String::new();
+        channel.read_to_string(&mut stdout)?;
+        let mut stderr = String::new();
+        channel.stderr().read_to_string(&mut stderr)?;
+
+        channel.wait_close()?;
+
+        let exit_code = channel.exit_status()?;
+
+        Ok(json!({
+          "exit_code": exit_code,
+          "stdout": stdout,
+          "stderr": stderr,
+        }))
+    }
+}
+
+/// Build the command reading the script from its standard input, according
+/// to its shebang.
+fn detect_interpreter(script: &str) -> String {
+    let shebang = script
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("#!"))
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let command = match shebang.as_slice() {
+        [] => vec!["sh"],
+        [env, rest @ ..] if env.ends_with("/env") && !rest.is_empty() => rest.to_vec(),
+        _ => shebang.clone(),
+    };
+
+    let program = command[0].rsplit('/').next().unwrap_or(command[0]);
+    let stdin_flag = if SHELLS.contains(&program) {
+        "-s --"
+    } else {
+        "-"
+    };
+
+    format!("{} {}", command.join(" "), stdin_flag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_interpreter_should_use_shebang() {
+        assert_eq!(detect_interpreter("echo hi\n"), "sh -s --");
+        assert_eq!(
+            detect_interpreter("#!/bin/bash\necho hi\n"),
+            "/bin/bash -s --"
+        );
+        assert_eq!(
+            detect_interpreter("#!/usr/bin/env python3\nprint('hi')\n"),
+            "python3 -"
+        );
+        assert_eq!(
+            detect_interpreter("#!/usr/bin/perl -w\nprint 'hi';\n"),
+            "/usr/bin/perl -w -"
+        );
+    }
+}
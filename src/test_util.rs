@@ -0,0 +1,38 @@
+//! Helpers shared by the unit tests.
+
+use std::{
+    env, fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+/// Temporary directory, removed at the end of the test even if it fails
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    /// Create an empty directory, unique to the test `name` and to the process
+    pub fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join("tricorder-tests").join(format!(
+            "{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+
+impl Deref for TestDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
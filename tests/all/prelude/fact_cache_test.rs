@@ -0,0 +1,62 @@
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tricorder::prelude::*;
+use tricorder::tasks::exec;
+
+use crate::tasks::common::TestDir;
+
+fn setup_facts() -> HashMap<String, Value> {
+    let mut facts = HashMap::new();
+    facts.insert("os".to_string(), json!({"id": "debian"}));
+    facts
+}
+
+#[test]
+fn load_should_populate_host_facts() {
+    let dir = TestDir::new("load");
+    let cache = FactCache::new(dir.to_path_buf(), Duration::from_secs(3600));
+    let mut host = Host::new(Host::id("example-0").unwrap(), "127.0.1.1:22".to_string());
+
+    assert!(!cache.load(&mut host).unwrap());
+    assert!(host.facts.is_empty());
+
+    cache.set(&host.id, &setup_facts()).unwrap();
+
+    assert!(cache.load(&mut host).unwrap());
+    assert_eq!(host.facts, setup_facts());
+}
+
+#[test]
+fn get_should_ignore_stale_facts() {
+    let dir = TestDir::new("stale");
+    let id = Host::id("example-0").unwrap();
+    let cache = FactCache::new(dir.to_path_buf(), Duration::from_secs(3600));
+
+    cache.set(&id, &setup_facts()).unwrap();
+    assert_eq!(cache.get(&id).unwrap(), Some(setup_facts()));
+
+    // facts gathered two hours ago, with a TTL of one hour
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let entry = json!({
+      "host": "example-0",
+      "gathered_at": now.as_secs() - 7200,
+      "facts": setup_facts(),
+    });
+    fs::write(dir.join("example-0.json"), entry.to_string()).unwrap();
+
+    assert_eq!(cache.get(&id).unwrap(), None);
+}
+
+#[test]
+fn facts_should_be_available_to_templates() {
+    let host = Host::new(Host::id("example-0").unwrap(), "127.0.1.1:22".to_string())
+        .set_facts(setup_facts())
+        .to_owned();
+
+    let task = exec::Task::new("echo {host.facts.os.id}".to_string());
+    assert_eq!(task.prepare(host).unwrap(), "echo debian".to_string());
+}
@@ -0,0 +1,94 @@
+use tricorder::prelude::{Host, HostPattern, Inventory};
+
+#[test]
+fn exact_pattern_should_match_only_the_host_id() {
+    let pattern = HostPattern::new("web-1").unwrap();
+
+    assert!(pattern.is_match(&Host::id("web-1").unwrap()));
+    assert!(!pattern.is_match(&Host::id("web-10").unwrap()));
+}
+
+#[test]
+fn glob_pattern_should_work() {
+    let pattern = HostPattern::new("web-*").unwrap();
+
+    assert!(pattern.is_match(&Host::id("web-1").unwrap()));
+    assert!(pattern.is_match(&Host::id("web-").unwrap()));
+    assert!(!pattern.is_match(&Host::id("db-1").unwrap()));
+
+    let pattern = HostPattern::new("db-0[1-3]").unwrap();
+
+    assert!(pattern.is_match(&Host::id("db-02").unwrap()));
+    assert!(!pattern.is_match(&Host::id("db-04").unwrap()));
+
+    let pattern = HostPattern::new("db-?[!0]").unwrap();
+
+    assert!(pattern.is_match(&Host::id("db-01").unwrap()));
+    assert!(!pattern.is_match(&Host::id("db-10").unwrap()));
+}
+
+#[test]
+fn regex_pattern_should_work() {
+    let pattern = HostPattern::new(r"~^web-\d+$").unwrap();
+
+    assert!(pattern.is_match(&Host::id("web-42").unwrap()));
+    assert!(!pattern.is_match(&Host::id("web-a").unwrap()));
+}
+
+#[test]
+fn invalid_pattern_should_fail() {
+    assert!(HostPattern::new("").is_err());
+    assert!(HostPattern::new("db-[12").is_err());
+    assert!(HostPattern::new("~web-(").is_err());
+}
+
+#[test]
+fn get_hosts_by_patterns_should_work() {
+    let inventory = Inventory::new()
+        .add_host(Host::new(
+            Host::id("web-1").unwrap(),
+            "web-1:22".to_string(),
+        ))
+        .add_host(Host::new(
+            Host::id("web-2").unwrap(),
+            "web-2:22".to_string(),
+        ))
+        .add_host(Host::new(
+            Host::id("db-01").unwrap(),
+            "db-01:22".to_string(),
+        ))
+        .add_host(Host::new(
+            Host::id("db-04").unwrap(),
+            "db-04:22".to_string(),
+        ))
+        .to_owned();
+
+    let patterns = HostPattern::parse_list("db-0[1-3], web-*, web-1, cache").unwrap();
+    assert_eq!(patterns.len(), 4);
+
+    let host_ids: Vec<String> = inventory
+        .get_hosts_by_patterns(&patterns)
+        .into_iter()
+        .map(|host| host.id.to_string())
+        .collect();
+
+    assert_eq!(host_ids, vec!["web-1", "web-2", "db-01"]);
+}
+
+#[test]
+fn parse_list_should_not_split_inside_regex_groups() {
+    let patterns =
+        HostPattern::parse_list(r"~^web-\d{1,3}$, db-0[1-3],~^(db|cache)-[1,3]$").unwrap();
+    let patterns: Vec<&str> = patterns.iter().map(|pattern| pattern.as_str()).collect();
+
+    assert_eq!(
+        patterns,
+        vec![r"~^web-\d{1,3}$", "db-0[1-3]", "~^(db|cache)-[1,3]$"]
+    );
+
+    let pattern = HostPattern::parse_list(r"~^web-\d{1,3}$")
+        .unwrap()
+        .remove(0);
+    assert!(pattern.is_match(&Host::id("web-042").unwrap()));
+    assert!(!pattern.is_match(&Host::id("web-0420").unwrap()));
+}
@@ -1,4 +1,9 @@
-use tricorder::prelude::{Host, Inventory};
+use tricorder::prelude::{Host, Inventory, TagExpr};
+
+use serde_json::json;
+use std::fs;
+
+use crate::tasks::common::TestDir;
 
 #[test]
 fn new_should_create_an_empty_inventory() {
@@ -226,3 +231,529 @@ fn get_host_by_tags_should_work() {
         }
     }
 }
+
+#[test]
+fn get_host_by_tag_expr_should_work() {
+    let inventory = Inventory::new()
+        .add_host(
+            Host::new(Host::id("web-0").unwrap(), "127.0.1.1:22".to_string())
+                .add_tag(Host::tag("dc:paris").unwrap())
+                .to_owned(),
+        )
+        .add_host(
+            Host::new(Host::id("web-1").unwrap(), "127.0.1.2:22".to_string())
+                .add_tag(Host::tag("dc:lyon").unwrap())
+                .to_owned(),
+        )
+        .to_owned();
+
+    let expr = TagExpr::parse("dc:* & !dc:lyon").unwrap();
+    let hosts = inventory.get_hosts_by_tag_expr(&expr);
+
+    assert_eq!(hosts.len(), 1);
+    assert_eq!(hosts[0].id, Host::id("web-0").unwrap());
+
+    let expr = TagExpr::tag_glob("dc:*")
+        .unwrap()
+        .and(!TagExpr::tag(Host::tag("dc:paris").unwrap()));
+    let hosts = inventory.get_hosts_by_tag_expr(&expr);
+
+    assert_eq!(hosts.len(), 1);
+    assert_eq!(hosts[0].id, Host::id("web-1").unwrap());
+}
+
+#[test]
+fn from_toml_should_apply_group_settings() {
+    let content = r#"
+  [groups.prod]
+  user = "deploy"
+  vars = { env = "prod", http_port = 80 }
+
+  [groups.web]
+  groups = ["prod"]
+  tags = ["web"]
+  vars = { http_port = 8080 }
+
+  [[hosts]]
+
+  id = "web-0"
+  address = "127.0.1.1:22"
+  groups = ["web"]
+  vars = { http_port = 8081 }
+
+  [[hosts]]
+
+  id = "web-1"
+  address = "127.0.1.2:22"
+  user = "admin"
+  groups = ["web"]
+
+  [[hosts]]
+
+  id = "db-0"
+  address = "127.0.1.3:22"
+  "#;
+
+    let inventory = Inventory::from_toml(content).unwrap();
+
+    let host = inventory
+        .get_host_by_id(Host::id("web-0").unwrap())
+        .expect("host web-0 should exist");
+    assert_eq!(host.user(), "deploy");
+    assert_eq!(host.tags, vec![Host::tag("web").unwrap()]);
+    assert_eq!(host.vars["env"], json!("prod"));
+    assert_eq!(host.vars["http_port"], json!(8081));
+
+    let host = inventory
+        .get_host_by_id(Host::id("web-1").unwrap())
+        .expect("host web-1 should exist");
+    assert_eq!(host.user(), "admin");
+    assert_eq!(host.vars["http_port"], json!(8080));
+
+    let host = inventory
+        .get_host_by_id(Host::id("db-0").unwrap())
+        .expect("host db-0 should exist");
+    assert_eq!(host.user(), "root");
+    assert!(host.vars.is_empty());
+}
+
+#[test]
+fn from_toml_should_apply_group_connection_settings() {
+    let content = r#"
+  [defaults]
+  port = 2222
+  connect_timeout = 10
+
+  [groups.web]
+  port = 2200
+  connect_timeout = 5
+
+  [[hosts]]
+
+  id = "web-0"
+  address = "127.0.1.1"
+  groups = ["web"]
+
+  [[hosts]]
+
+  id = "web-1"
+  address = "127.0.1.2:22"
+  groups = ["web"]
+  connect_timeout = 1
+
+  [[hosts]]
+
+  id = "db-0"
+  address = "127.0.1.3"
+  "#;
+
+    let inventory = Inventory::from_toml(content).unwrap();
+
+    assert_eq!(inventory.hosts[0].address, String::from("127.0.1.1:2200"));
+    assert_eq!(inventory.hosts[0].connect_timeout, Some(5));
+    assert_eq!(inventory.hosts[1].address, String::from("127.0.1.2:22"));
+    assert_eq!(inventory.hosts[1].connect_timeout, Some(1));
+    assert_eq!(inventory.hosts[2].address, String::from("127.0.1.3:2222"));
+    assert_eq!(inventory.hosts[2].connect_timeout, Some(10));
+}
+
+#[test]
+fn host_should_default_to_root_user() {
+    let host: Host = serde_json::from_str(r#"{"id": "web-0", "address": "127.0.1.1:22"}"#).unwrap();
+
+    assert_eq!(host.user, None);
+    assert_eq!(host.user(), "root");
+    assert_eq!(serde_json::to_value(&host).unwrap()["user"], json!("root"));
+}
+
+#[test]
+fn from_json_should_apply_group_settings() {
+    let content = r#"
+  {"hosts": [
+    {"id": "web-0", "address": "127.0.1.1:22", "groups": ["web"]}
+  ], "groups": {
+    "web": {"user": "deploy", "vars": {"env": "prod"}}
+  }}
+  "#;
+
+    let inventory = Inventory::from_json(content).unwrap();
+    let host = &inventory.hosts[0];
+
+    assert_eq!(host.user(), "deploy");
+    assert_eq!(host.vars["env"], json!("prod"));
+}
+
+#[test]
+fn from_toml_should_fail_on_invalid_groups() {
+    let content = r#"
+  [[hosts]]
+
+  id = "example-0"
+  address = "127.0.1.1:22"
+  groups = ["missing"]
+  "#;
+
+    assert!(Inventory::from_toml(content).is_err());
+
+    let content = r#"
+  [groups.a]
+  groups = ["b"]
+
+  [groups.b]
+  groups = ["a"]
+
+  [[hosts]]
+
+  id = "example-0"
+  address = "127.0.1.1:22"
+  groups = ["a"]
+  "#;
+
+    match Inventory::from_toml(content) {
+        Ok(_) => assert!(false, "cyclic groups should not be resolved"),
+        Err(err) => assert!(err.to_string().contains("a -> b -> a")),
+    };
+}
+
+#[test]
+fn from_toml_should_apply_defaults() {
+    let content = r#"
+  [defaults]
+  user = "admin"
+  port = 2222
+  connect_timeout = 10
+  vars = { env = "prod", region = "eu" }
+
+  [groups.staging]
+  user = "deploy"
+  vars = { env = "staging" }
+
+  [[hosts]]
+
+  id = "example-0"
+  address = "127.0.1.1"
+
+  [[hosts]]
+
+  id = "example-1"
+  address = "127.0.1.2:22"
+  groups = ["staging"]
+  connect_timeout = 5
+
+  [[hosts]]
+
+  id = "example-2"
+  address = "::1"
+  user = "root"
+  vars = { region = "us" }
+  "#;
+
+    let inventory = Inventory::from_toml(content).unwrap();
+
+    let host = &inventory.hosts[0];
+    assert_eq!(host.address, String::from("127.0.1.1:2222"));
+    assert_eq!(host.user(), "admin");
+    assert_eq!(host.connect_timeout, Some(10));
+    assert_eq!(host.vars["env"], json!("prod"));
+
+    let host = &inventory.hosts[1];
+    assert_eq!(host.address, String::from("127.0.1.2:22"));
+    assert_eq!(host.user(), "deploy");
+    assert_eq!(host.connect_timeout, Some(5));
+    assert_eq!(host.vars["env"], json!("staging"));
+    assert_eq!(host.vars["region"], json!("eu"));
+
+    let host = &inventory.hosts[2];
+    assert_eq!(host.address, String::from("[::1]:2222"));
+    assert_eq!(host.user(), "root");
+    assert_eq!(host.vars["region"], json!("us"));
+}
+
+#[test]
+fn from_json_should_apply_defaults() {
+    let content = r#"
+  {"defaults": {"user": "admin", "vars": {"env": "prod"}}, "hosts": [
+    {"id": "example-0", "address": "127.0.1.1"}
+  ]}
+  "#;
+
+    let inventory = Inventory::from_json(content).unwrap();
+    let host = &inventory.hosts[0];
+
+    assert_eq!(host.address, String::from("127.0.1.1:22"));
+    assert_eq!(host.user(), "admin");
+    assert_eq!(host.vars["env"], json!("prod"));
+}
+
+#[test]
+fn from_toml_should_expand_host_ranges() {
+    let content = r#"
+  [[hosts]]
+
+  id = "web-[08:10]"
+  address = "10.0.1.[18:20]:22"
+  tags = ["web"]
+
+  [[hosts]]
+
+  id = "db-[1:2]"
+  address = "10.0.2.1"
+  vars = { role = "db" }
+  "#;
+
+    let inventory = Inventory::from_toml(content).unwrap();
+    let ids: Vec<String> = inventory
+        .hosts
+        .iter()
+        .map(|host| host.id.clone().to_string())
+        .collect();
+
+    assert_eq!(ids, vec!["web-08", "web-09", "web-10", "db-1", "db-2"]);
+
+    let host = &inventory.hosts[1];
+    assert_eq!(host.address, String::from("10.0.1.19:22"));
+    assert_eq!(host.tags, vec![Host::tag("web").unwrap()]);
+    assert_eq!(host.vars["range_index"], json!(9));
+
+    let host = &inventory.hosts[4];
+    assert_eq!(host.address, String::from("10.0.2.1:22"));
+    assert_eq!(host.vars["role"], json!("db"));
+    assert_eq!(host.vars["range_index"], json!(2));
+}
+
+#[test]
+fn from_json_should_expand_host_ranges() {
+    let content = r#"
+  {"hosts": [
+    {"id": "web-[1:2]", "address": "10.0.1.[10:11]"}
+  ]}
+  "#;
+
+    let inventory = Inventory::from_json(content).unwrap();
+
+    assert_eq!(inventory.hosts.len(), 2);
+    assert_eq!(inventory.hosts[1].address, String::from("10.0.1.11:22"));
+}
+
+#[test]
+fn from_toml_should_fail_on_mismatched_host_ranges() {
+    let content = r#"
+  [[hosts]]
+
+  id = "web-[01:40]"
+  address = "10.0.1.[10:20]:22"
+  "#;
+
+    match Inventory::from_toml(content) {
+        Ok(_) => panic!("Mismatched host ranges should not be accepted"),
+        Err(err) => assert!(err.to_string().contains("has 40 hosts")),
+    }
+}
+
+#[test]
+fn from_paths_should_merge_inventories() {
+    let dir = TestDir::new("merge-inventories");
+    let extra = dir.join("extra");
+    fs::create_dir_all(&extra).unwrap();
+
+    fs::write(
+        dir.join("b.json"),
+        r#"{"hosts": [{"id": "db-1", "address": "10.0.2.1"}]}"#,
+    )
+    .unwrap();
+    fs::write(
+        dir.join("a.toml"),
+        "[defaults]\nuser = \"admin\"\n[[hosts]]\nid = \"web-1\"\naddress = \"10.0.1.1\"\n",
+    )
+    .unwrap();
+    fs::write(dir.join("README.md"), "not an inventory").unwrap();
+    fs::write(
+        extra.join("c.toml"),
+        "[[hosts]]\nid = \"web-2\"\naddress = \"10.0.1.2\"\n",
+    )
+    .unwrap();
+
+    let dir_path = dir.display().to_string();
+    let extra_path = extra.join("c.toml").display().to_string();
+    let inventory = Inventory::from_paths(&[&dir_path, &extra_path]).unwrap();
+
+    let ids: Vec<String> = inventory
+        .hosts
+        .iter()
+        .map(|host| host.id.clone().to_string())
+        .collect();
+    assert_eq!(ids, vec!["web-1", "db-1", "web-2"]);
+
+    // the defaults of all the inventories apply to all their hosts
+    assert_eq!(inventory.hosts[0].user(), "admin");
+    assert_eq!(inventory.hosts[1].user(), "admin");
+}
+
+#[test]
+fn from_paths_should_share_groups_and_defaults() {
+    let dir = TestDir::new("shared-groups");
+    fs::write(
+        dir.join("g.toml"),
+        "[defaults]\nvars = { env = \"prod\" }\n[groups.web]\nport = 2222\ntags = [\"web\"]\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("h.toml"),
+        "[[hosts]]\nid = \"web-1\"\naddress = \"10.0.1.1\"\ngroups = [\"web\"]\n",
+    )
+    .unwrap();
+
+    let dir_path = dir.display().to_string();
+    let inventory = Inventory::from_paths(&[&dir_path]).unwrap();
+
+    let host = &inventory.hosts[0];
+    assert_eq!(host.address, String::from("10.0.1.1:2222"));
+    assert_eq!(host.tags, vec![Host::tag("web").unwrap()]);
+    assert_eq!(host.vars["env"], json!("prod"));
+
+    fs::write(dir.join("i.toml"), "[defaults]\nvars = { env = \"dev\" }\n").unwrap();
+
+    match Inventory::from_paths(&[&dir_path]) {
+        Ok(_) => panic!("Conflicting defaults should not be accepted"),
+        Err(err) => {
+            let message = err.to_string();
+            assert!(message.contains("Default 'vars.env' is defined differently in"));
+            assert!(message.contains("g.toml"));
+            assert!(message.contains("i.toml"));
+        }
+    }
+}
+
+#[test]
+fn from_paths_should_fail_on_duplicate_host_ids() {
+    let dir = TestDir::new("duplicate-host-ids");
+    let content = "[[hosts]]\nid = \"web-1\"\naddress = \"10.0.1.1\"\n";
+    fs::write(dir.join("a.toml"), content).unwrap();
+    fs::write(dir.join("b.toml"), content).unwrap();
+
+    let dir_path = dir.display().to_string();
+
+    match Inventory::from_paths(&[&dir_path]) {
+        Ok(_) => panic!("Duplicate host ids should not be accepted"),
+        Err(err) => {
+            let message = err.to_string();
+            assert!(message.contains("Host 'web-1' is defined in both"));
+            assert!(message.contains("a.toml"));
+            assert!(message.contains("b.toml"));
+        }
+    }
+}
+
+#[test]
+fn from_paths_should_fail_on_conflicting_groups() {
+    let dir = TestDir::new("conflicting-groups");
+    fs::write(
+        dir.join("a.toml"),
+        "[[hosts]]\nid = \"web-1\"\naddress = \"10.0.1.1\"\n[groups.web]\nport = 2222\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("b.toml"),
+        "[[hosts]]\nid = \"web-2\"\naddress = \"10.0.1.2\"\n[groups.web]\nport = 2222\n",
+    )
+    .unwrap();
+
+    let dir_path = dir.display().to_string();
+    let inventory = Inventory::from_paths(&[&dir_path]).unwrap();
+    assert_eq!(inventory.groups.len(), 1);
+
+    fs::write(
+        dir.join("c.toml"),
+        "[[hosts]]\nid = \"web-3\"\naddress = \"10.0.1.3\"\n[groups.web]\nport = 22\n",
+    )
+    .unwrap();
+
+    match Inventory::from_paths(&[&dir_path]) {
+        Ok(_) => panic!("Conflicting groups should not be accepted"),
+        Err(err) => {
+            let message = err.to_string();
+            assert!(message.contains("Group 'web' is defined differently in"));
+            assert!(message.contains("a.toml"));
+            assert!(message.contains("c.toml"));
+        }
+    }
+}
+
+#[test]
+fn from_file_should_detect_json_inventories() {
+    let dir = TestDir::new("json-inventories");
+    let content = r#"{"hosts": [{"id": "db-1", "address": "10.0.2.1"}]}"#;
+    fs::write(dir.join("cmdb.json"), content).unwrap();
+    fs::write(dir.join("cmdb-export"), content).unwrap();
+
+    for name in ["cmdb.json", "cmdb-export"] {
+        let path = dir.join(name).display().to_string();
+        let inventory = Inventory::from_file(&path).unwrap();
+
+        assert_eq!(inventory.hosts.len(), 1);
+        assert_eq!(inventory.hosts[0].address, String::from("10.0.2.1:22"));
+    }
+}
+
+#[test]
+fn from_file_should_name_the_parser_on_error() {
+    let dir = TestDir::new("invalid-inventories");
+    fs::write(dir.join("broken.json"), r#"{"hosts": [}"#).unwrap();
+    fs::write(dir.join("broken.toml"), "[[hosts]\n").unwrap();
+
+    let path = dir.join("broken.json").display().to_string();
+    match Inventory::from_file(&path) {
+        Ok(_) => panic!("Invalid JSON inventory should not be accepted"),
+        Err(err) => assert!(err.to_string().contains("broken.json' as JSON")),
+    }
+
+    let path = dir.join("broken.toml").display().to_string();
+    match Inventory::from_file(&path) {
+        Ok(_) => panic!("Invalid TOML inventory should not be accepted"),
+        Err(err) => assert!(err.to_string().contains("broken.toml' as TOML")),
+    }
+}
+
+#[test]
+fn from_file_should_report_the_location_of_errors() {
+    let dir = TestDir::new("error-locations");
+    fs::write(
+        dir.join("hosts.toml"),
+        "[[hosts]]\nid = \"web-1\"\naddress = \"10.0.1.1\"\n\n[[hosts]]\nid = \"web 2\"\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("hosts.json"),
+        "{\"hosts\": [\n  {\"id\": \"web-2\", \"address\": \"10.0.1.2\", \"tags\": \"web\"}\n]}",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("range.toml"),
+        "[defaults]\nport = -1\n[[hosts]]\nid = \"web 2\"\naddress = \"10.0.1.2\"\n[[hosts]]\nid = \"web-[1:3]\"\naddress = \"10.0.1.[1:3]\"\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("range-hosts.toml"),
+        "[[hosts]]\nid = \"web-[1:3]\"\naddress = \"10.0.1.[1:3]\"\ntags = \"web\"\n",
+    )
+    .unwrap();
+
+    let errors: Vec<String> = ["hosts.toml", "hosts.json", "range.toml", "range-hosts.toml"]
+        .iter()
+        .map(|name| {
+            let path = dir.join(name).display().to_string();
+            match Inventory::from_file(&path) {
+                Ok(_) => panic!("Invalid inventory '{}' should not be accepted", name),
+                Err(err) => err.to_string(),
+            }
+        })
+        .collect();
+
+    // without host ranges, the errors keep their line and column
+    assert!(errors[0].contains("at line 5 column 1"), "{}", errors[0]);
+    assert!(errors[1].contains("at line 2 column"), "{}", errors[1]);
+    // otherwise, they name the section they come from
+    assert!(errors[2].contains("in defaults"), "{}", errors[2]);
+    assert!(!errors[2].contains("in host"), "{}", errors[2]);
+    assert!(errors[3].contains("in host 'web-1'"), "{}", errors[3]);
+}
@@ -1,2 +1,8 @@
+#[path = "fact_cache_test.rs"]
+mod fact_cache_test;
+
+#[path = "host_pattern_test.rs"]
+mod host_pattern_test;
+
 #[path = "inventory_test.rs"]
 mod inventory_test;
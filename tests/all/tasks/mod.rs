@@ -2,3 +2,6 @@ pub mod common;
 
 #[path = "exec_test.rs"]
 mod exec_test;
+
+#[path = "module_test.rs"]
+mod module_test;
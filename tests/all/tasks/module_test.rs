@@ -0,0 +1,68 @@
+use serde_json::json;
+use std::fs;
+use tricorder::prelude::*;
+use tricorder::tasks::module;
+
+use super::common::TestDir;
+
+/// Create a module and its data file, removed with the returned directory
+fn setup_module_task(name: &str, data: &str) -> (TestDir, module::Task) {
+    let dir = TestDir::new(name);
+
+    let module_path = dir.join("mod.sh");
+    let data_path = dir.join("data.json");
+    fs::write(&module_path, "#!/bin/sh\ncat\n").unwrap();
+    fs::write(&data_path, data).unwrap();
+
+    let task = module::Task::new(
+        Some(data_path.to_string_lossy().to_string()),
+        module_path.to_string_lossy().to_string(),
+    );
+
+    (dir, task)
+}
+
+#[test]
+fn prepare_should_merge_data_file_host_vars_and_args() {
+    let (_dir, mut task) = setup_module_task(
+        "module-merge",
+        r#"{"from_file": 1, "from_vars": 1, "from_args": 1, "nested": {"a": 1, "b": 1}}"#,
+    );
+    task.set_args(json!({"from_args": 3, "nested": {"b": 3}}));
+
+    let host = Host::new(Host::id("localhost").unwrap(), "localhost:22".to_string())
+        .set_var(
+            "module_mod.sh".to_string(),
+            json!({"from_vars": 2, "from_args": 2}),
+        )
+        .to_owned();
+
+    assert_eq!(
+        task.prepare(host).unwrap(),
+        json!({
+          "from_file": 1,
+          "from_vars": 2,
+          "from_args": 3,
+          "nested": {"a": 1, "b": 3}
+        })
+    );
+}
+
+#[test]
+fn prepare_should_render_templated_data_file() {
+    let (_dir, mut task) = setup_module_task(
+        "module-template",
+        r#"\{"server_name": "{host.id}.example.com", "listen": "{host.address}"}"#,
+    );
+    task.set_template(true);
+
+    let host = Host::new(Host::id("localhost").unwrap(), "localhost:22".to_string());
+
+    assert_eq!(
+        task.prepare(host).unwrap(),
+        json!({
+          "server_name": "localhost.example.com",
+          "listen": "localhost:22"
+        })
+    );
+}